@@ -0,0 +1,151 @@
+//! Indexing of a generated workspace by the server binary, talking LSP
+//! over its standard input and output as an editor would, from the
+//! `initialized` notification to the `angular/status` one ending indexing.
+//! Run with `cargo bench`, comparing a single worker with the default ones.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_json::{json, Value};
+
+/// Number of component files of the generated workspace.
+const FILE_COUNT: usize = 400;
+
+/// A workspace folder of `FILE_COUNT` components with a few inputs and outputs each.
+fn generate_workspace() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("angular-lsp-bench-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for index in 0..FILE_COUNT {
+        let folder = root.join(format!("src/app/item-{}", index));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join(format!("item-{}.component.ts", index)), format!(r#"import {{ Component, EventEmitter, Input, Output }} from '@angular/core';
+
+/** Item number {index} of the generated workspace. */
+@Component({{
+  selector: 'app-item-{index}',
+  templateUrl: './item-{index}.component.html',
+}})
+export class Item{index}Component {{
+  @Input() title: string;
+  @Input() count = 0;
+  @Input({{ required: true }}) item: unknown;
+  @Output() selected = new EventEmitter<number>();
+
+  select() {{
+    this.selected.emit(this.count);
+  }}
+}}
+"#)).unwrap();
+    }
+    root
+}
+
+/// The server process, along with the pipes of its LSP messages.
+struct Server {
+    process: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+}
+
+impl Server {
+    fn start() -> Server {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_angular-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Error starting the server");
+        let input = process.stdin.take().unwrap();
+        let output = BufReader::new(process.stdout.take().unwrap());
+        Server { process, input, output }
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.output.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length: ") {
+                length = value.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Reads messages until `done` accepts one, answering the server's requests on the way.
+    fn receive_until(&mut self, done: impl Fn(&Value) -> bool) {
+        loop {
+            let message = self.receive();
+            if done(&message) {
+                return;
+            }
+            if let (Some(id), Some(_)) = (message.get("id"), message.get("method")) {
+                self.send(json!({ "jsonrpc": "2.0", "id": id, "result": null }));
+            }
+        }
+    }
+
+    fn stop(mut self) {
+        self.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }));
+        self.receive_until(|message| message["id"] == 2);
+        self.send(json!({ "jsonrpc": "2.0", "method": "exit" }));
+        // The server only stops once its input is closed
+        drop(self.input);
+        self.process.wait().unwrap();
+    }
+}
+
+/// Time the server takes to index `root` with `workers` indexing workers, the default ones for none.
+fn index_workspace(root: &Path, workers: Option<usize>) -> Duration {
+    let mut server = Server::start();
+    let root_uri = format!("file://{}", root.display());
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "capabilities": {},
+            "workspaceFolders": [{ "uri": root_uri, "name": "bench" }],
+            "initializationOptions": { "indexingWorkers": workers, "logLevel": "error" },
+        },
+    }));
+    server.receive_until(|message| message["id"] == 1);
+    let start = Instant::now();
+    server.send(json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }));
+    server.receive_until(|message| message["method"] == "angular/status" && message["params"]["indexing"] == false);
+    let time = start.elapsed();
+    server.stop();
+    time
+}
+
+fn indexing(c: &mut Criterion) {
+    let root = generate_workspace();
+    let mut group = c.benchmark_group("indexing");
+    group.sample_size(10);
+    for (name, workers) in [("sequential", Some(1)), ("parallel", None)] {
+        group.bench_with_input(BenchmarkId::new(name, FILE_COUNT), &workers, |b, workers| {
+            b.iter_custom(|iterations| (0..iterations).map(|_| index_workspace(&root, *workers)).sum());
+        });
+    }
+    group.finish();
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, indexing);
+criterion_main!(benches);
@@ -29,9 +29,13 @@ export async function activate(context: ExtensionContext) {
         debug: run,
     };
     const clientOptions: LanguageClientOptions = {
-        documentSelector: [ "html" ],
+        documentSelector: [ "html", "typescript", "typescriptreact" ],
         synchronize: {
-            fileEvents: workspace.createFileSystemWatcher("**/*.ts"),
+            fileEvents: [
+                workspace.createFileSystemWatcher("**/*.{ts,mts,cts,tsx}"),
+                workspace.createFileSystemWatcher("**/*.{html,htm}"),
+                workspace.createFileSystemWatcher("**/angular.json"),
+            ],
         },
     };
 
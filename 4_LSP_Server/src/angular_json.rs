@@ -0,0 +1,79 @@
+//! Source roots of the projects of a workspace folder, read from its `angular.json`.
+
+use std::fs;
+use std::path::Path;
+
+use log::warn;
+use serde_json::Value;
+
+use crate::{template_globs_under, typescript_globs};
+
+pub const FILE_NAME: &str = "angular.json";
+
+/// Source roots of the projects, none without an `angular.json` giving any.
+pub fn source_roots(workspace_root: &str) -> Option<Vec<String>> {
+    let path = Path::new(workspace_root).join(FILE_NAME);
+    let contents = fs::read_to_string(&path).ok()?;
+    let workspace: Value = match serde_json::from_str(&contents) {
+        Ok(workspace) => workspace,
+        Err(e) => {
+            warn!("Error parsing {:?} {:?}", path, e);
+            return None;
+        }
+    };
+    let mut source_roots: Vec<String> = Vec::new();
+    let projects = workspace.get("projects").and_then(Value::as_object).into_iter().flatten();
+    for (_, project) in projects {
+        let Some(source_root) = project.get("sourceRoot").and_then(Value::as_str) else {
+            continue;
+        };
+        if !source_roots.iter().any(|known| known == source_root) {
+            source_roots.push(String::from(source_root));
+        }
+    }
+    (!source_roots.is_empty()).then_some(source_roots)
+}
+
+/// Patterns of the TypeScript sources of each project `sourceRoot`, none without an `angular.json`
+/// giving source roots.
+pub fn component_globs(workspace_root: &str) -> Option<Vec<String>> {
+    Some(source_roots(workspace_root)?.iter().flat_map(|source_root| typescript_globs(source_root)).collect())
+}
+
+/// Patterns of the templates of each project `sourceRoot`, by their `extensions`.
+pub fn template_globs(workspace_root: &str, extensions: &[String]) -> Option<Vec<String>> {
+    Some(source_roots(workspace_root)?.iter().flat_map(|source_root| template_globs_under(source_root, extensions)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::folder;
+
+    const WORKSPACE: &str = r#"{
+  "projects": {
+    "web": { "root": "apps/web", "sourceRoot": "apps/web/src" },
+    "admin": { "root": "apps/admin", "sourceRoot": "apps/web/src" },
+    "tools": { "root": "tools" }
+  }
+}"#;
+
+    #[test]
+    fn globs_follow_the_project_source_roots() {
+        let root = folder(&[(FILE_NAME, WORKSPACE)]);
+        let root = root.to_str().unwrap();
+        assert_eq!(source_roots(root), Some(vec![String::from("apps/web/src")]));
+        assert_eq!(component_globs(root), Some(typescript_globs("apps/web/src")));
+        assert_eq!(template_globs(root, &[String::from("html")]), Some(vec![String::from("apps/web/src/**/*.html")]));
+    }
+
+    #[test]
+    fn missing_or_broken_workspaces_give_no_globs() {
+        let missing = folder(&[]);
+        assert_eq!(component_globs(missing.to_str().unwrap()), None);
+        let broken = folder(&[(FILE_NAME, "{ \"projects\": ")]);
+        assert_eq!(component_globs(broken.to_str().unwrap()), None);
+        let without_roots = folder(&[(FILE_NAME, r#"{ "projects": { "tools": { "root": "tools" } } }"#)]);
+        assert_eq!(source_roots(without_roots.to_str().unwrap()), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! Closing of the brackets and quotes of a binding attribute as it gets typed,
+//! like `]` after `<app-btn [`, through on-type formatting.
+
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::{byte_to_point, find_node, to_position};
+
+pub const FIRST_TRIGGER_CHARACTER: &str = "[";
+pub const MORE_TRIGGER_CHARACTERS: &[&str] = &["(", "\""];
+
+fn char_at(rope: &Rope, offset: usize) -> Option<char> {
+    rope.get_byte_slice(offset..)?.chars().next()
+}
+
+fn char_before(rope: &Rope, offset: usize) -> Option<char> {
+    let before = rope.get_byte_slice(..offset)?;
+    before.get_char(before.len_chars().checked_sub(1)?)
+}
+
+/// Whether `offset` is between the attributes of a tag, out of their values.
+fn in_attributes(root: &Node, offset: usize) -> bool {
+    // A quote just opened leaves the rest of the template unparsable, the tag included
+    let Some(tag) = find_node(root, offset, vec![ "start_tag", "self_closing_tag", "ERROR" ]) else {
+        return false;
+    };
+    let Some(tag_name) = tag.named_child(0).filter(|tag_name| tag_name.kind() == "tag_name") else {
+        return false;
+    };
+    offset > tag_name.end_byte() && find_node(&tag, offset, vec![ "quoted_attribute_value" ])
+        .is_none_or(|quoted| quoted.start_byte() == offset)
+}
+
+/// What closes `typed` at the start of a binding attribute, none elsewhere:
+/// `[` and `(` opening a name, `(` within `[` for a two-way binding, or the `"` opening a value.
+fn closing(typed: char, before: Option<char>) -> Option<char> {
+    let after_space = before.is_some_and(char::is_whitespace);
+    match typed {
+        '[' if after_space => Some(']'),
+        '(' if after_space || before == Some('[') => Some(')'),
+        '"' if before == Some('=') => Some('"'),
+        _ => None,
+    }
+}
+
+/// Edit inserting what closes the character `typed` right before `offset`, unless it's already there.
+pub fn closing_edits(root: &Node, offset: usize, rope: &Rope, typed: &str) -> Vec<TextEdit> {
+    let mut chars = typed.chars();
+    let (Some(typed), None) = (chars.next(), chars.next()) else {
+        return Vec::new();
+    };
+    let Some(typed_offset) = offset.checked_sub(typed.len_utf8()) else {
+        return Vec::new();
+    };
+    if char_at(rope, typed_offset) != Some(typed) || !in_attributes(root, typed_offset) {
+        return Vec::new();
+    }
+    let Some(closing) = closing(typed, char_before(rope, typed_offset)) else {
+        return Vec::new();
+    };
+    // Like when the client closed it already
+    if char_at(rope, offset) == Some(closing) {
+        return Vec::new();
+    }
+    let position = to_position(rope, offset, byte_to_point(rope, offset));
+    vec![TextEdit::new(Range::new(position, position), String::from(closing))]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::template;
+
+    /// What typing the character before the `|` of `text` inserts at the `|`.
+    fn closed(text: &str) -> Vec<String> {
+        let offset = text.find('|').unwrap();
+        let (rope, tree) = template(&text.replacen('|', "", 1));
+        let typed = rope.byte_slice(..offset).chars().last().unwrap().to_string();
+        closing_edits(&tree.root_node(), offset, &rope, &typed).into_iter()
+            .map(|edit| edit.new_text)
+            .collect()
+    }
+
+    #[test]
+    fn binding_brackets_and_quotes_get_closed() {
+        assert_eq!(closed("<app-btn [|></app-btn>"), ["]"]);
+        assert_eq!(closed("<app-btn (|></app-btn>"), [")"]);
+        // Within `[` for a two-way binding
+        assert_eq!(closed("<app-btn [(|></app-btn>"), [")"]);
+        assert_eq!(closed("<app-btn [title]=\"|></app-btn>"), ["\""]);
+    }
+
+    #[test]
+    fn brackets_elsewhere_stay_open() {
+        // In a quoted value or in text
+        assert!(closed("<app-btn title=\"a [|\"></app-btn>").is_empty());
+        assert!(closed("<p>see [|</p>").is_empty());
+        // Already closed, like by the client
+        assert!(closed("<app-btn [|]></app-btn>").is_empty());
+        // The quote closing a value
+        assert!(closed("<app-btn title=\"a\"|></app-btn>").is_empty());
+    }
+}
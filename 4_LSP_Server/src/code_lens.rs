@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use ropey::Rope;
+use serde_json::json;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::index::ComponentIndex;
+use crate::{element_name_range, walk_tree, ComponentKind};
+
+/// Command of the usage lenses, given the document URL and the position, which the server
+/// answers with the references there, for the client to show.
+pub const SHOW_REFERENCES_COMMAND: &str = "angular.showReferences";
+
+fn count(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+/// Lenses over each known component tag summing up the inputs and outputs it offers,
+/// and how many times it's used in the workspace templates, as counted in `usages`.
+pub fn binding_lenses(root: &Node, rope: &Rope, components: &ComponentIndex, usages: &HashMap<String, usize>) -> Vec<CodeLens> {
+    let mut lenses = Vec::new();
+    walk_tree(root, &mut |node| {
+        let in_start_tag = node.parent()
+            .is_some_and(|parent| parent.kind() == "start_tag" || parent.kind() == "self_closing_tag");
+        if node.kind() != "tag_name" || !in_start_tag {
+            return;
+        }
+        let Some((name, range)) = element_name_range(&node, rope) else {
+            return;
+        };
+        let Some(component) = components.get(&name)
+            .filter(|component| component.kind == ComponentKind::Component) else {
+            return;
+        };
+        lenses.push(CodeLens {
+            range,
+            // Only informative, the lens has nothing to run
+            command: Some(Command {
+                title: format!("{}, {}", count(component.inputs.len(), "input"), count(component.outputs.len(), "output")),
+                command: String::new(),
+                arguments: None,
+            }),
+            data: None,
+        });
+        // Arguments are only known once the range is in the document
+        lenses.push(CodeLens {
+            range,
+            command: Some(Command {
+                title: count(usages.get(&name).copied().unwrap_or(0), "usage"),
+                command: String::from(SHOW_REFERENCES_COMMAND),
+                arguments: None,
+            }),
+            data: None,
+        });
+    });
+    lenses
+}
+
+/// Fills in the arguments of a usage lens of the document `uri`, once at its final range.
+pub fn with_location(mut lens: CodeLens, uri: &Url) -> CodeLens {
+    if let Some(command) = lens.command.as_mut().filter(|command| command.command == SHOW_REFERENCES_COMMAND) {
+        command.arguments = Some(vec![json!(uri), json!(lens.range.start)]);
+    }
+    lens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{file_url, index, template};
+
+    #[test]
+    fn usage_lenses_count_the_elements_of_the_component() {
+        let components = index(&[("card.component.ts", "@Component({ selector: 'app-card', template: '' })\nexport class CardComponent {\n  @Input() title: string;\n}")]);
+        let (rope, tree) = template("<app-card></app-card><app-card></app-card><p></p>");
+        let usages = HashMap::from([(String::from("app-card"), 2), (String::from("p"), 1)]);
+        let lenses: Vec<CodeLens> = binding_lenses(&tree.root_node(), &rope, &components, &usages).into_iter()
+            .map(|lens| with_location(lens, &file_url("list.component.html")))
+            .collect();
+        let titles: Vec<&str> = lenses.iter()
+            .filter_map(|lens| lens.command.as_ref())
+            .map(|command| command.title.as_str())
+            .collect();
+        assert_eq!(titles, ["1 input, 0 outputs", "2 usages", "1 input, 0 outputs", "2 usages"]);
+        let command = lenses[3].command.as_ref().unwrap();
+        assert_eq!(command.command, SHOW_REFERENCES_COMMAND);
+        assert_eq!(command.arguments, Some(vec![json!(file_url("list.component.html")), json!(Position::new(0, 22))]));
+    }
+}
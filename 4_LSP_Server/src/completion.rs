@@ -0,0 +1,701 @@
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::export_as;
+use crate::expression::pipe_at;
+use crate::index::ComponentIndex;
+use crate::style_classes;
+use crate::{attribute_selector, bindable_components, find_node, hover_contents, node_text, parse_binding, BindingKind, Component, ComponentKind, Property};
+
+/// Built-in structural directives, available on any element, with their snippet.
+static STRUCTURAL_DIRECTIVES: &[(&str, &str)] = &[
+    ("*ngIf", "*ngIf=\"$1\""),
+    ("*ngFor", "*ngFor=\"let $1 of $2\""),
+    ("*ngSwitchCase", "*ngSwitchCase=\"$1\""),
+    ("*ngSwitchDefault", "*ngSwitchDefault"),
+];
+
+/// Bindings of `<ng-template>`, the desugared form of structural directives, with their snippet.
+static NG_TEMPLATE_BINDINGS: &[(&str, &str)] = &[
+    ("[ngTemplateOutlet]", "[ngTemplateOutlet]=\"$1\""),
+    ("[ngTemplateOutletContext]", "[ngTemplateOutletContext]=\"$1\""),
+    ("[ngIf]", "[ngIf]=\"$1\""),
+    ("[ngForOf]", "[ngForOf]=\"$1\""),
+];
+
+/// A common attribute directive of the Angular packages, available on any element
+/// once its module is imported.
+pub struct BuiltinDirective {
+    pub attribute: &'static str,
+    pub snippet: &'static str,
+    pub package: &'static str,
+    /// Binding names of its inputs, the attribute itself included when it takes a value.
+    pub inputs: &'static [&'static str],
+    pub outputs: &'static [&'static str],
+}
+
+pub static BUILTIN_ATTRIBUTE_DIRECTIVES: &[BuiltinDirective] = &[
+    BuiltinDirective {
+        attribute: "routerLink",
+        snippet: "[routerLink]=\"[$0]\"",
+        package: "@angular/router",
+        inputs: &["routerLink", "queryParams", "fragment", "queryParamsHandling", "state", "relativeTo",
+            "preserveFragment", "skipLocationChange", "replaceUrl"],
+        outputs: &[],
+    },
+    BuiltinDirective {
+        attribute: "routerLinkActive",
+        snippet: "routerLinkActive=\"$0\"",
+        package: "@angular/router",
+        inputs: &["routerLinkActive", "routerLinkActiveOptions", "ariaCurrentWhenActive"],
+        outputs: &["isActiveChange"],
+    },
+    BuiltinDirective {
+        attribute: "ngModel",
+        snippet: "[(ngModel)]=\"$0\"",
+        package: "@angular/forms",
+        inputs: &["ngModel", "ngModelOptions", "name", "disabled"],
+        outputs: &["ngModelChange"],
+    },
+    BuiltinDirective {
+        attribute: "formControl",
+        snippet: "[formControl]=\"$0\"",
+        package: "@angular/forms",
+        inputs: &["formControl"],
+        outputs: &[],
+    },
+    BuiltinDirective {
+        attribute: "formControlName",
+        snippet: "formControlName=\"$0\"",
+        package: "@angular/forms",
+        inputs: &["formControlName"],
+        outputs: &[],
+    },
+    BuiltinDirective {
+        attribute: "formGroup",
+        snippet: "[formGroup]=\"$0\"",
+        package: "@angular/forms",
+        inputs: &["formGroup"],
+        outputs: &["ngSubmit"],
+    },
+    BuiltinDirective {
+        attribute: "ngClass",
+        snippet: "[ngClass]=\"$0\"",
+        package: "@angular/common",
+        inputs: &["ngClass"],
+        outputs: &[],
+    },
+    BuiltinDirective {
+        attribute: "ngStyle",
+        snippet: "[ngStyle]=\"$0\"",
+        package: "@angular/common",
+        inputs: &["ngStyle"],
+        outputs: &[],
+    },
+    BuiltinDirective {
+        attribute: "ngSwitch",
+        snippet: "[ngSwitch]=\"$0\"",
+        package: "@angular/common",
+        inputs: &["ngSwitch"],
+        outputs: &[],
+    },
+];
+
+/// HTML attributes valid on any element, components included.
+static GLOBAL_ATTRIBUTES: &[&str] = &[
+    "class", "dir", "hidden", "id", "lang", "role", "style", "tabindex", "title",
+];
+
+/// ARIA attributes, for native elements.
+static ARIA_ATTRIBUTES: &[&str] = &[
+    "aria-checked", "aria-controls", "aria-current", "aria-describedby", "aria-disabled",
+    "aria-expanded", "aria-haspopup", "aria-hidden", "aria-label", "aria-labelledby", "aria-live",
+    "aria-pressed", "aria-selected",
+];
+
+/// Attributes of the common native elements taking some of their own.
+static ELEMENT_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "rel", "target"]),
+    ("button", &["disabled", "name", "type", "value"]),
+    ("form", &["action", "method", "novalidate"]),
+    ("img", &["alt", "height", "loading", "src", "width"]),
+    ("input", &["autocomplete", "checked", "disabled", "max", "maxlength", "min", "name", "placeholder", "readonly", "required", "type", "value"]),
+    ("label", &["for"]),
+    ("select", &["disabled", "multiple", "name", "required"]),
+    ("textarea", &["disabled", "maxlength", "name", "placeholder", "readonly", "required", "rows"]),
+];
+
+/// ARIA roles, the values of the `role` attribute.
+static ARIA_ROLES: &[&str] = &[
+    "alert", "button", "checkbox", "dialog", "grid", "link", "list", "listitem", "menu", "menuitem",
+    "navigation", "presentation", "progressbar", "radio", "region", "search", "status", "tab",
+    "tablist", "tabpanel", "textbox",
+];
+
+/// Elements of Angular itself, which render no element of their own, with their documentation.
+static ANGULAR_ELEMENTS: &[(&str, &str)] = &[
+    ("ng-container", "Groups elements without adding one to the DOM, like to apply a structural directive."),
+    ("ng-content", "Where the content projected into the component goes, narrowed by its `select` attribute."),
+    ("ng-template", "Content that is only rendered when a directive or `[ngTemplateOutlet]` instantiates it."),
+];
+
+/// Control flow blocks that may start a template, with their snippet.
+static CONTROL_FLOW_BLOCKS: &[(&str, &str)] = &[
+    ("@if", "if (${1:condition}) {\n\t$0\n}"),
+    ("@for", "for (${1:item} of ${2:items}; track ${3:$1}) {\n\t$0\n}"),
+    ("@switch", "switch (${1:value}) {\n\t@case (${2:case}) {\n\t\t$0\n\t}\n}"),
+    ("@defer", "defer {\n\t$0\n}"),
+];
+
+/// Pipes provided by `@angular/common`.
+static BUILTIN_PIPES: &[&str] = &[
+    "async", "currency", "date", "decimal", "i18nPlural", "i18nSelect", "json", "keyvalue",
+    "lowercase", "percent", "slice", "titlecase", "uppercase",
+];
+
+/// What a completion item completes, so that `resolve` may document it later.
+#[derive(Serialize, Deserialize)]
+struct CompletionData {
+    selector: String,
+    /// Binding name of the completed input or output, none for the tag itself.
+    property: Option<String>,
+    output: bool,
+}
+
+fn completion_data(selector: &str, property: Option<&Property>, output: bool) -> Option<serde_json::Value> {
+    let data = CompletionData {
+        selector: String::from(selector),
+        property: property.map(|property| String::from(property.binding_name())),
+        output,
+    };
+    serde_json::to_value(data).ok()
+}
+
+/// Inputs `x` paired with an `xChange` output, which `model()` signals
+/// declare implicitly, and thus support the `[(x)]` two-way binding.
+fn two_way_bindings(inputs: &[Property], outputs: &[Property]) -> Vec<Property> {
+    inputs.iter()
+        .filter(|input| outputs.iter().any(|output| output.binding_name() == format!("{}Change", input.binding_name())))
+        .cloned()
+        .collect()
+}
+
+/// Path of `url` in the workspace folder holding it, or its file name outside of them.
+fn relative_path(url: &Url, roots: &[String]) -> String {
+    let path = url.path();
+    roots.iter()
+        .find_map(|root| path.strip_prefix(root.as_str()).filter(|rest| rest.starts_with('/')))
+        .map(|rest| rest.trim_start_matches('/'))
+        .or_else(|| path.rsplit('/').next())
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+fn tag_completions(components: &ComponentIndex, roots: &[String]) -> Vec<CompletionItem> {
+    components.iter()
+        .filter(|component| component.kind == ComponentKind::Component && attribute_selector(&component.selector).is_none())
+        .map(|component| CompletionItem {
+            label: component.selector.clone(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            // Documentation waits for resolve, the detail tells apart components of the same selector
+            detail: Some(format!("{} · {}", component.class_name, relative_path(&component.file_url, roots))),
+            data: completion_data(&component.selector, None, false),
+            ..Default::default()
+        })
+        .chain(ANGULAR_ELEMENTS.iter().map(|(element, documentation)| CompletionItem {
+            label: String::from(*element),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(String::from("@angular/core")),
+            documentation: Some(markdown(String::from(*documentation))),
+            ..Default::default()
+        }))
+        .collect()
+}
+
+fn pipe_completions(components: &ComponentIndex) -> Vec<CompletionItem> {
+    let mut completions: Vec<CompletionItem> = components.pipes()
+        .map(|pipe| CompletionItem {
+            label: pipe.name.clone(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: Some(pipe.class_name.clone()),
+            ..Default::default()
+        })
+        .collect();
+    completions.extend(BUILTIN_PIPES.iter()
+        .filter(|name| components.pipe(name).is_none())
+        .map(|name| CompletionItem {
+            label: String::from(*name),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: Some(String::from("@angular/common")),
+            ..Default::default()
+        }));
+    completions
+}
+
+/// Snippets leaving out the `@`, which clients don't take as part of the typed word.
+fn block_completions() -> Vec<CompletionItem> {
+    CONTROL_FLOW_BLOCKS.iter()
+        .map(|(label, snippet)| CompletionItem {
+            label: String::from(*label),
+            kind: Some(CompletionItemKind::KEYWORD),
+            filter_text: Some(String::from(&label[1..])),
+            insert_text: Some(String::from(*snippet)),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Value snippet of a binding, its default value as a placeholder when it has one.
+fn value_snippet(property: &Property) -> String {
+    match &property.default_value {
+        Some(default_value) => {
+            let escaped = default_value.replace('\\', "\\\\").replace('$', "\\$").replace('}', "\\}");
+            format!("=\"${{1:{}}}\"", escaped.replace('"', "'"))
+        }
+        None => String::from("=\"$0\""),
+    }
+}
+
+fn attribute_completions(start_tag: &Node, rope: &Rope, components: &ComponentIndex) -> Vec<CompletionItem> {
+    fn make_completions(selector: &str, elements: &[Property], template: &str, output: bool) -> Vec<CompletionItem> {
+        elements.iter().map(|input| CompletionItem {
+            label: String::from(input.binding_name()),
+            kind: Some(CompletionItemKind::FIELD),
+            filter_text: Some(template.replace("{}", input.binding_name())),
+            insert_text: Some(template.replace("{}", input.binding_name()) + &value_snippet(input)),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            data: completion_data(selector, Some(input), output),
+            ..Default::default()
+        }).collect()
+    }
+    let mut completions = Vec::new();
+    for component in bindable_components(start_tag, rope, components) {
+        completions.append(&mut make_completions(&component.selector, &component.inputs, "[{}]", false));
+        completions.append(&mut make_completions(&component.selector, &component.outputs, "({})", true));
+        completions.extend(two_way_bindings(&component.inputs, &component.outputs).iter()
+            .map(|input| CompletionItem {
+                label: format!("[({})]", input.binding_name()),
+                kind: Some(CompletionItemKind::FIELD),
+                insert_text: Some(format!("[({})]=\"$0\"", input.binding_name())),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                data: completion_data(&component.selector, Some(input), false),
+                ..Default::default()
+            }));
+    }
+    if start_tag.named_child(0).and_then(|tag_name| node_text(&tag_name, rope)).as_deref() == Some("ng-template") {
+        completions.extend(NG_TEMPLATE_BINDINGS.iter().map(|(label, snippet)| CompletionItem {
+            label: String::from(*label),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: Some(String::from("@angular/common")),
+            insert_text: Some(String::from(*snippet)),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        }));
+        // Context variables, `let-item="$implicit"`
+        completions.push(CompletionItem {
+            label: String::from("let-"),
+            kind: Some(CompletionItemKind::VARIABLE),
+            insert_text: Some(String::from("let-${1:name}=\"${2:\\$implicit}\"")),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        });
+    }
+    let tag_name = start_tag.named_child(0).and_then(|tag_name| node_text(&tag_name, rope)).unwrap_or_default();
+    completions.extend(html_attribute_completions(&tag_name, components));
+    completions.extend(STRUCTURAL_DIRECTIVES.iter().map(|(label, snippet)| CompletionItem {
+        label: String::from(*label),
+        kind: Some(CompletionItemKind::KEYWORD),
+        insert_text: Some(String::from(*snippet)),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }));
+    completions.extend(BUILTIN_ATTRIBUTE_DIRECTIVES.iter().map(|directive| CompletionItem {
+        label: String::from(directive.attribute),
+        kind: Some(CompletionItemKind::PROPERTY),
+        detail: Some(String::from(directive.package)),
+        insert_text: Some(String::from(directive.snippet)),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }));
+    completions.extend(components.iter()
+        .filter_map(|directive| attribute_selector(&directive.selector).map(String::from))
+        .map(|attribute| CompletionItem {
+            label: attribute,
+            kind: Some(CompletionItemKind::PROPERTY),
+            ..Default::default()
+        }));
+    completions
+}
+
+/// Native attributes of `tag_name`: only the global ones for components,
+/// along with ARIA attributes and those of the element for native elements.
+fn html_attribute_completions(tag_name: &str, components: &ComponentIndex) -> Vec<CompletionItem> {
+    let native = !tag_name.contains('-') && !components.contains_key(tag_name);
+    let element_attributes = ELEMENT_ATTRIBUTES.iter()
+        .filter(|(element, _)| native && element.eq_ignore_ascii_case(tag_name))
+        .flat_map(|(_, attributes)| attributes.iter());
+    let aria_attributes = ARIA_ATTRIBUTES.iter().filter(|_| native);
+    GLOBAL_ATTRIBUTES.iter().chain(aria_attributes).chain(element_attributes)
+        .map(|attribute| CompletionItem {
+            label: String::from(*attribute),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some(String::from("HTML")),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Roles offered in the value of a `role` attribute.
+fn role_completions(start_tag: &Node, offset: usize, rope: &Rope) -> Option<Vec<CompletionItem>> {
+    let quoted = find_node(start_tag, offset, vec![ "quoted_attribute_value" ])?;
+    if offset <= quoted.start_byte() || offset >= quoted.end_byte() {
+        return None;
+    }
+    if node_text(&quoted.parent()?.named_child(0)?, rope)? != "role" {
+        return None;
+    }
+    let typed = rope.get_byte_slice(quoted.start_byte() + 1..offset).map(String::from)?;
+    let completions = ARIA_ROLES.iter()
+        .map(|role| CompletionItem {
+            label: String::from(*role),
+            kind: Some(CompletionItemKind::ENUM_MEMBER),
+            ..Default::default()
+        })
+        .collect();
+    Some(filter_completions(completions, &typed))
+}
+
+/// Allowed values of the input whose attribute value is under `offset`, either
+/// plain like `variant="primary"` or a bound string like `[variant]="'primary'"`.
+fn value_completions(start_tag: &Node, offset: usize, rope: &Rope, components: &ComponentIndex) -> Option<Vec<CompletionItem>> {
+    let quoted = find_node(start_tag, offset, vec![ "quoted_attribute_value" ])?;
+    if offset <= quoted.start_byte() || offset >= quoted.end_byte() {
+        return None;
+    }
+    let attribute_name = node_text(&quoted.parent()?.named_child(0)?, rope)?;
+    let (bound, name) = match parse_binding(&attribute_name) {
+        Some((BindingKind::Property, name)) => (true, String::from(name)),
+        Some(_) => return None,
+        None => (false, attribute_name.clone()),
+    };
+    let input = bindable_components(start_tag, rope, components).into_iter()
+        .flat_map(|component| component.inputs)
+        .find(|input| input.binding_name() == name && !input.allowed_values.is_empty())?;
+
+    let typed = rope.get_byte_slice(quoted.start_byte() + 1..offset).map(String::from)?;
+    // A bound value is an expression, where the literal may not be opened yet
+    let in_string = bound && typed.matches('\'').count() % 2 == 1;
+    let typed = typed.rsplit('\'').next().unwrap_or_default();
+    let completions = input.allowed_values.iter()
+        .map(|value| CompletionItem {
+            label: value.clone(),
+            kind: Some(CompletionItemKind::ENUM_MEMBER),
+            insert_text: Some(if bound && !in_string { format!("'{}'", value) } else { value.clone() }),
+            ..Default::default()
+        })
+        .collect();
+    Some(filter_completions(completions, typed))
+}
+
+/// Name of an attribute or a completion without its binding decorations.
+fn bare_name(text: &str) -> String {
+    text.trim_matches(|c| matches!(c, '[' | ']' | '(' | ')' | '*')).to_lowercase()
+}
+
+/// Keeps the completions whose filter text contains what was typed so far,
+/// setting `filter_text` so that the client filters the same way.
+fn filter_completions(completions: Vec<CompletionItem>, typed: &str) -> Vec<CompletionItem> {
+    let typed = bare_name(typed);
+    completions.into_iter()
+        .map(|completion| CompletionItem {
+            filter_text: Some(completion.filter_text.clone().unwrap_or_else(|| completion.label.clone())),
+            ..completion
+        })
+        .filter(|completion| completion.filter_text.as_deref().is_some_and(|text| bare_name(text).contains(&typed)))
+        .collect()
+}
+
+/// Rank of the group of a completion: structural directives and other keywords,
+/// then inputs, then outputs, then everything else.
+fn completion_group(completion: &CompletionItem) -> u8 {
+    match completion.kind {
+        Some(CompletionItemKind::KEYWORD) => 0,
+        Some(CompletionItemKind::FIELD) if completion.filter_text.as_deref().unwrap_or(&completion.label).starts_with('(') => 2,
+        Some(CompletionItemKind::FIELD) => 1,
+        _ => 3,
+    }
+}
+
+/// Drops the completions repeating the label and kind of an earlier one, as when several
+/// directives bind the same name, and sorts them by group then name whatever the order
+/// of the index, `sort_text` keeping that order on the client.
+fn ordered_completions(completions: Vec<CompletionItem>) -> Vec<CompletionItem> {
+    let mut unique: Vec<CompletionItem> = Vec::with_capacity(completions.len());
+    for completion in completions {
+        if !unique.iter().any(|known| known.label == completion.label && known.kind == completion.kind) {
+            unique.push(completion);
+        }
+    }
+    unique.sort_by(|a, b| (completion_group(a), bare_name(&a.label), &a.label).cmp(&(completion_group(b), bare_name(&b.label), &b.label)));
+    unique.into_iter()
+        .enumerate()
+        .map(|(index, completion)| CompletionItem { sort_text: Some(format!("{:04}", index)), ..completion })
+        .collect()
+}
+
+/// Text of the line before `offset`.
+fn line_before(offset: usize, rope: &Rope) -> Option<String> {
+    let line_start = rope.try_line_to_byte(rope.try_byte_to_line(offset).ok()?).ok()?;
+    rope.get_byte_slice(line_start..offset).map(String::from)
+}
+
+fn follows_whitespace(offset: usize, rope: &Rope) -> bool {
+    line_before(offset, rope).is_some_and(|before| before.ends_with(char::is_whitespace))
+}
+
+/// Tag name typed right after a `<` that isn't parsed as a tag yet, like a lone `<`.
+fn typed_tag_name(offset: usize, rope: &Rope) -> Option<String> {
+    let before = line_before(offset, rope)?;
+    let name_start = before.trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '-').len();
+    before[..name_start].ends_with('<').then(|| String::from(&before[name_start..]))
+}
+
+/// Block name typed after an `@` in text, which isn't part of a word like an email address.
+fn typed_block_name(offset: usize, rope: &Rope) -> Option<String> {
+    let before = line_before(offset, rope)?;
+    let name_start = before.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len();
+    let at = before[..name_start].strip_suffix('@')?;
+    (!at.ends_with(|c: char| c.is_alphanumeric())).then(|| String::from(&before[name_start..]))
+}
+
+/// Completions at `offset` of a template, declared by `owner` if known, `roots` being the workspace folders.
+pub fn completion(node: &Node, offset: usize, rope: &Rope, components: &ComponentIndex, owner: Option<&Component>, roots: &[String]) -> Vec<CompletionItem> {
+    ordered_completions(unordered_completion(node, offset, rope, components, owner, roots))
+}
+
+fn unordered_completion(node: &Node, offset: usize, rope: &Rope, components: &ComponentIndex, owner: Option<&Component>,
+        roots: &[String]) -> Vec<CompletionItem> {
+    if let Some((_, range)) = pipe_at(node, offset, rope) {
+        let typed = rope.get_byte_slice(range.start..offset)
+            .map(String::from)
+            .unwrap_or_default();
+        return filter_completions(pipe_completions(components), &typed);
+    }
+    let Some(start_tag) = find_node(node, offset, vec![ "start_tag", "self_closing_tag" ]) else {
+        if let Some(typed) = typed_block_name(offset, rope) {
+            return filter_completions(block_completions(), &typed);
+        }
+        return match typed_tag_name(offset, rope) {
+            Some(typed) => filter_completions(tag_completions(components, roots), &typed),
+            None => Vec::new(),
+        };
+    };
+    if let Some(values) = value_completions(&start_tag, offset, rope, components) {
+        return values;
+    }
+    if let Some(exports) = export_as::completions(&start_tag, offset, rope, components) {
+        return exports;
+    }
+    if let Some(roles) = role_completions(&start_tag, offset, rope) {
+        return roles;
+    }
+    if let Some(classes) = style_classes::completions(&start_tag, offset, rope, owner) {
+        return classes;
+    }
+    let (token, completions) = if let Some(tag_name) = find_node(&start_tag, offset, vec![ "tag_name" ]) {
+        (tag_name, tag_completions(components, roots))
+    } else if let Some(attribute_name) = find_node(&start_tag, offset, vec![ "attribute_name" ]) {
+        (attribute_name, attribute_completions(&start_tag, rope, components))
+    } else if find_node(&start_tag, offset, vec![ "attribute" ]).is_none() && follows_whitespace(offset, rope) {
+        // Right after a space between attributes, nothing typed yet
+        return attribute_completions(&start_tag, rope, components);
+    } else {
+        return Vec::new();
+    };
+    let typed = rope.get_byte_slice(token.start_byte()..offset.clamp(token.start_byte(), token.end_byte()))
+        .map(String::from)
+        .unwrap_or_default();
+    filter_completions(completions, &typed)
+}
+
+/// Fills in the detail and documentation of the completion the user selected.
+pub fn resolve(item: CompletionItem, components: &ComponentIndex) -> CompletionItem {
+    let Some(data) = item.data.clone().and_then(|data| serde_json::from_value::<CompletionData>(data).ok()) else {
+        return item;
+    };
+    let Some(component) = components.get(&data.selector) else {
+        return item;
+    };
+    let Some(binding_name) = data.property else {
+        return CompletionItem {
+            detail: item.detail.clone().or_else(|| Some(component.class_name.clone())),
+            documentation: Some(markdown(hover_contents(&component))),
+            ..item
+        };
+    };
+    let properties = if data.output { &component.outputs } else { &component.inputs };
+    let Some(property) = properties.iter().find(|property| property.binding_name() == binding_name) else {
+        return item;
+    };
+    let detail = match &property.value_type {
+        Some(value_type) => format!("{}: {}", property.name, value_type),
+        None => property.name.clone(),
+    };
+    let role = if data.output { "Output" } else { "Input" };
+    let mut documentation = format!("{} of `{}`", role, component.class_name);
+    if let Some(comment) = &property.documentation {
+        documentation = format!("{}\n\n{}", comment, documentation);
+    }
+    CompletionItem {
+        detail: Some(detail),
+        documentation: Some(markdown(documentation)),
+        ..item
+    }
+}
+
+fn markdown(value: String) -> Documentation {
+    Documentation::MarkupContent(MarkupContent { kind: MarkupKind::Markdown, value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ComponentIndex;
+    use crate::testing::{index, template};
+
+    const TOOLTIP: &str = r#"
+@Directive({ selector: '[appTooltip]' })
+export class TooltipDirective {
+  @Input() appTooltip: string;
+  @Input() tooltipPosition: string;
+  @Output() shown = new EventEmitter<void>();
+}"#;
+
+    /// Completions of `text` at its `|`, which is left out of the template.
+    fn completions_at(text: &str, components: &ComponentIndex) -> Vec<CompletionItem> {
+        let offset = text.find('|').unwrap();
+        let (rope, tree) = template(&text.replace('|', ""));
+        completion(&tree.root_node(), offset, &rope, components, None, &[String::from("/workspace")])
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[test]
+    fn applied_directives_offer_their_bindings() {
+        let components = index(&[("tooltip.directive.ts", TOOLTIP)]);
+        let items = completions_at("<div appTooltip |></div>", &components);
+        assert!(labels(&items).contains(&"tooltipPosition"));
+        assert!(labels(&items).contains(&"shown"));
+        // Without the directive the element has none of them
+        let items = completions_at("<div |></div>", &components);
+        assert!(!labels(&items).contains(&"tooltipPosition"));
+    }
+
+    #[test]
+    fn tag_completions_tell_where_components_come_from() {
+        let components = index(&[("src/app/card.component.ts", r#"
+/** A card with a title. */
+@Component({ selector: 'app-card', template: '' })
+export class CardComponent {
+  @Input() title: string;
+}"#)]);
+        let items = completions_at("<app-|", &components);
+        let item = items.iter().find(|item| item.label == "app-card").unwrap();
+        assert_eq!(item.detail.as_deref(), Some("CardComponent · src/app/card.component.ts"));
+        // The documentation waits for the item to be resolved
+        assert!(item.documentation.is_none());
+        let Some(Documentation::MarkupContent(documentation)) = resolve(item.clone(), &components).documentation else {
+            panic!("No documentation");
+        };
+        assert_eq!(documentation.value, "**CardComponent** `<app-card>`\n\nA card with a title.\n\nInputs:\n- `title`");
+    }
+
+    fn item(label: &str, kind: CompletionItemKind) -> CompletionItem {
+        CompletionItem { label: String::from(label), kind: Some(kind), ..Default::default() }
+    }
+
+    #[test]
+    fn completions_are_unique_and_ordered_by_group_then_name() {
+        let items = vec![
+            item("(closed)", CompletionItemKind::FIELD),
+            item("title", CompletionItemKind::FIELD),
+            item("*ngIf", CompletionItemKind::KEYWORD),
+            item("class", CompletionItemKind::PROPERTY),
+            item("[size]", CompletionItemKind::FIELD),
+            item("title", CompletionItemKind::FIELD),
+            item("*ngFor", CompletionItemKind::KEYWORD),
+        ];
+        let mut reversed = items.clone();
+        reversed.reverse();
+        let ordered = ordered_completions(items);
+        assert_eq!(labels(&ordered), ["*ngFor", "*ngIf", "[size]", "title", "(closed)", "class"]);
+        let sort_texts: Vec<_> = ordered.iter().map(|item| item.sort_text.as_deref().unwrap()).collect();
+        assert_eq!(sort_texts, ["0000", "0001", "0002", "0003", "0004", "0005"]);
+        // Whatever order the index gave them in
+        assert_eq!(ordered_completions(reversed), ordered);
+    }
+
+    #[test]
+    fn ng_templates_offer_their_bindings() {
+        let items = completions_at("<ng-template [ngTemplateOutlet]=\"row\" |></ng-template>", &ComponentIndex::default());
+        assert!(labels(&items).contains(&"[ngTemplateOutletContext]"));
+        let context = items.iter().find(|item| item.label == "[ngTemplateOutletContext]").unwrap();
+        assert_eq!(context.insert_text.as_deref(), Some("[ngTemplateOutletContext]=\"$1\""));
+        // Other elements don't get them
+        assert!(!labels(&completions_at("<div |></div>", &ComponentIndex::default())).contains(&"[ngTemplateOutletContext]"));
+    }
+
+    #[test]
+    fn input_defaults_are_snippet_placeholders() {
+        let components = index(&[("button.component.ts", r#"
+@Component({ selector: 'app-button', template: '' })
+export class ButtonComponent {
+  @Input() size = 'md';
+  variant = input("primary");
+  @Input() label: string;
+  @Output() pressed = new EventEmitter<void>();
+}"#)]);
+        let items = completions_at("<app-button |></app-button>", &components);
+        let insert_text = |label: &str| items.iter().find(|item| item.label == label).and_then(|item| item.insert_text.clone());
+        assert_eq!(insert_text("size").as_deref(), Some("[size]=\"${1:'md'}\""));
+        // Double quotes would end the attribute value
+        assert_eq!(insert_text("variant").as_deref(), Some("[variant]=\"${1:'primary'}\""));
+        assert_eq!(insert_text("label").as_deref(), Some("[label]=\"$0\""));
+        assert_eq!(insert_text("pressed").as_deref(), Some("(pressed)=\"$0\""));
+    }
+
+    #[test]
+    fn angular_elements_are_offered_with_components() {
+        let components = index(&[("card.component.ts", "@Component({ selector: 'ng-card', template: '' })\nexport class CardComponent {}")]);
+        let items = completions_at("<div><ng-c|</div>", &components);
+        assert_eq!(labels(&items), ["ng-card", "ng-container", "ng-content"]);
+        let container = items.iter().find(|item| item.label == "ng-container").unwrap();
+        assert_eq!(container.kind, Some(CompletionItemKind::KEYWORD));
+        assert_eq!(container.detail.as_deref(), Some("@angular/core"));
+        assert!(container.documentation.is_some());
+    }
+
+    #[test]
+    fn svg_elements_offer_component_bindings() {
+        let components = index(&[("icon.component.ts", "@Component({ selector: 'app-icon', template: '' })\nexport class IconComponent {\n  @Input() name: string;\n}")]);
+        assert!(labels(&completions_at("<svg><app-icon |></app-icon></svg>", &components)).contains(&"name"));
+        assert!(labels(&completions_at("<svg><svg:app-icon |></svg:app-icon></svg>", &components)).contains(&"name"));
+        assert!(labels(&completions_at("<svg><g><app-|</g></svg>", &components)).contains(&"app-icon"));
+    }
+
+    #[test]
+    fn builtin_directives_are_offered_with_component_bindings() {
+        let components = index(&[("tooltip.directive.ts", TOOLTIP)]);
+        let items = completions_at("<a href=\"/\" |></a>", &components);
+        let router_link = items.iter().find(|item| item.label == "routerLink").unwrap();
+        assert_eq!(router_link.insert_text.as_deref(), Some("[routerLink]=\"[$0]\""));
+        assert_eq!(router_link.detail.as_deref(), Some("@angular/router"));
+        let offered = labels(&items);
+        assert!(["ngModel", "ngClass", "ngStyle", "appTooltip"].iter().all(|label| offered.contains(label)));
+    }
+}
@@ -0,0 +1,121 @@
+use log::warn;
+use serde::Deserialize;
+use serde_json::Value;
+use tower_lsp::lsp_types::MessageType;
+
+/// Source root of a workspace folder without an `angular.json` to tell its own.
+pub const DEFAULT_SOURCE_ROOT: &str = "src";
+
+/// Most verbose kind of messages logged to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warning,
+    Info,
+    /// Every document event, along with its timing.
+    Debug,
+}
+
+impl LogLevel {
+    pub fn message_type(self) -> MessageType {
+        match self {
+            LogLevel::Error => MessageType::ERROR,
+            LogLevel::Warning => MessageType::WARNING,
+            LogLevel::Info => MessageType::INFO,
+            LogLevel::Debug => MessageType::LOG,
+        }
+    }
+}
+
+/// Server settings, read from the client's `initializationOptions`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    /// Patterns, relative to each workspace folder, of the files to index components from.
+    /// By default the TypeScript sources of the `angular.json` projects, or of [`DEFAULT_SOURCE_ROOT`].
+    pub component_globs: Option<Vec<String>>,
+    pub log_level: LogLevel,
+    /// Number of files indexed at the same time, by default one per core up to a limit.
+    pub indexing_workers: Option<usize>,
+    /// Extensions of the documents analyzed as HTML templates, TypeScript inline templates aside.
+    pub template_extensions: Vec<String>,
+    /// Prefixes of the selectors of the workspace, like `app-`: unknown elements
+    /// are only reported with one of them, when any is given.
+    pub component_prefixes: Vec<String>,
+    /// Whether workspace folders are indexed once added, rather than only on demand.
+    pub index_on_startup: bool,
+    /// Size over which a file isn't indexed from disk, as generated and bundled sources can be huge.
+    pub max_file_size_bytes: u64,
+    /// Whether the index is saved in each workspace folder, the next start only analyzing the files modified since.
+    pub persist_index: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            component_globs: None,
+            log_level: LogLevel::Info,
+            indexing_workers: None,
+            template_extensions: vec![String::from("html"), String::from("htm")],
+            component_prefixes: Vec::new(),
+            index_on_startup: true,
+            max_file_size_bytes: 512 * 1024,
+            persist_index: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn is_template(&self, uri: &str) -> bool {
+        let path = uri.split(['?', '#']).next().unwrap_or(uri);
+        path.rsplit_once('.').is_some_and(|(file, extension)| !file.ends_with('/')
+            && self.template_extensions.iter().any(|template_extension| template_extension.eq_ignore_ascii_case(extension)))
+    }
+
+    pub fn from_options(options: Option<Value>) -> Config {
+        match options.map(serde_json::from_value::<Config>) {
+            Some(Ok(config)) => config,
+            Some(Err(e)) => {
+                warn!("Invalid initialization options {:?}", e);
+                Config::default()
+            }
+            None => Config::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn templates_have_a_configured_extension() {
+        let config = Config::default();
+        assert!(config.is_template("file:///app/list.component.html"));
+        assert!(config.is_template("file:///app/legacy.HTM"));
+        assert!(config.is_template("file:///app/list.html?version=2"));
+        assert!(!config.is_template("file:///app/README.md"));
+        assert!(!config.is_template("file:///app/list.component.ts"));
+        // Neither a hidden file nor a folder name is an extension
+        assert!(!config.is_template("file:///app/.html"));
+        assert!(!config.is_template("file:///app.html/README"));
+    }
+
+    #[test]
+    fn template_extensions_are_read_from_the_options() {
+        let config = Config::from_options(Some(json!({ "templateExtensions": ["svg"] })));
+        assert!(config.is_template("file:///app/icon.svg"));
+        assert!(!config.is_template("file:///app/list.html"));
+        // Other settings keep their default
+        assert_eq!(config.max_file_size_bytes, Config::default().max_file_size_bytes);
+    }
+
+    #[test]
+    fn component_prefixes_default_to_none() {
+        assert!(Config::default().component_prefixes.is_empty());
+        let config = Config::from_options(Some(json!({ "componentPrefixes": ["app-", "lib-"] })));
+        assert_eq!(config.component_prefixes, ["app-", "lib-"]);
+    }
+}
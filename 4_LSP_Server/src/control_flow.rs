@@ -0,0 +1,208 @@
+//! Control flow blocks like `@if (cond) { ... }`, which the HTML grammar only
+//! sees as text. Their syntax may even break the parse, as `@if (a < b)`
+//! opens a tag, so templates get parsed with the block syntax blanked out:
+//! the headers up to their `{` and the closing `}` become spaces, keeping
+//! byte offsets and lines intact, and what the blocks contain parses as usual.
+
+use std::ops::Range;
+
+use tree_sitter::{InputEdit, Point};
+
+/// Blocks of the Angular template syntax, including the ones chained to a previous block.
+static BLOCK_NAMES: &[&str] = &[
+    "if", "else", "for", "empty", "switch", "case", "default", "defer", "placeholder", "loading", "error",
+];
+
+fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// End of the `)` matching the `(` at `start`, skipping quoted strings.
+fn parameters_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (index, byte) in bytes.iter().enumerate().skip(start) {
+        match (quote, byte) {
+            (Some(open), _) if *byte == open => quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'' | b'`') => quote = Some(*byte),
+            (None, b'(') => depth += 1,
+            (None, b')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// End of the block header starting with the `@` at `start`, past its `{`.
+fn header_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let name_end = bytes[start + 1..].iter().position(|byte| !is_identifier_byte(*byte))
+        .map_or(bytes.len(), |end| start + 1 + end);
+    let name = std::str::from_utf8(&bytes[start + 1..name_end]).ok()?;
+    if !BLOCK_NAMES.contains(&name) {
+        return None;
+    }
+    // Parameters and keywords, like the `if (cond)` of `@else if (cond)`
+    let mut index = name_end;
+    while let Some(byte) = bytes.get(index) {
+        match byte {
+            b'{' => return Some(index + 1),
+            b'(' => index = parameters_end(bytes, index)?,
+            byte if byte.is_ascii_whitespace() || is_identifier_byte(*byte) => index += 1,
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn skip_past(bytes: &[u8], start: usize, end: &[u8]) -> usize {
+    bytes[start..].windows(end.len()).position(|window| window == end)
+        .map_or(bytes.len(), |index| start + index + end.len())
+}
+
+/// End of the tag starting at `start`, past its `>`, skipping quoted attribute values.
+fn tag_end(bytes: &[u8], start: usize) -> usize {
+    let mut quote = None;
+    for (index, byte) in bytes.iter().enumerate().skip(start) {
+        match (quote, byte) {
+            (Some(open), _) if *byte == open => quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => quote = Some(*byte),
+            (None, b'>') => return index + 1,
+            _ => {}
+        }
+    }
+    bytes.len()
+}
+
+/// Byte ranges of the block headers and closing braces of a template.
+fn block_syntax_ranges(text: &str) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut ranges = Vec::new();
+    let mut depth = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        let rest = &bytes[index..];
+        if rest.starts_with(b"<!--") {
+            index = skip_past(bytes, index, b"-->");
+        } else if rest[0] == b'<' && rest.get(1).is_some_and(|byte| byte.is_ascii_alphabetic() || *byte == b'/') {
+            index = tag_end(bytes, index);
+        } else if rest.starts_with(b"{{") {
+            index = skip_past(bytes, index, b"}}");
+        } else if let Some(end) = (rest[0] == b'@').then(|| header_end(bytes, index)).flatten() {
+            ranges.push(index..end);
+            depth += 1;
+            index = end;
+        } else if rest[0] == b'}' && depth > 0 {
+            ranges.push(index..index + 1);
+            depth -= 1;
+            index += 1;
+        } else {
+            index += 1;
+        }
+    }
+    ranges
+}
+
+/// `text` with its block syntax replaced by spaces, line breaks aside.
+pub fn mask_blocks(text: &str) -> String {
+    let mut bytes = text.as_bytes().to_vec();
+    for range in block_syntax_ranges(text) {
+        for byte in &mut bytes[range] {
+            if *byte != b'\n' && *byte != b'\r' {
+                *byte = b' ';
+            }
+        }
+    }
+    // Only whole characters are replaced, by as many ASCII spaces as they had bytes
+    String::from_utf8(bytes).unwrap_or_else(|_| String::from(text))
+}
+
+fn point_at(text: &str, byte: usize) -> Point {
+    let line_start = text[..byte].rfind('\n').map_or(0, |index| index + 1);
+    Point { row: text[..byte].matches('\n').count(), column: byte - line_start }
+}
+
+/// Edit turning the masked `old` text into the masked `new` one, spanning
+/// everything between their common prefix and suffix. An edit of the text
+/// can mask or unmask blocks away from it, like typing the `@` of a header
+/// whose `}` closes much further, so the edit of the text alone won't do.
+pub fn masked_edit(old: &str, new: &str) -> Option<InputEdit> {
+    let (old, new) = (mask_blocks(old), mask_blocks(new));
+    let (old_bytes, new_bytes) = (old.as_bytes(), new.as_bytes());
+    let mut start_byte = old_bytes.iter().zip(new_bytes).take_while(|(old, new)| old == new).count();
+    if start_byte == old_bytes.len() && start_byte == new_bytes.len() {
+        return None;
+    }
+    while !old.is_char_boundary(start_byte) || !new.is_char_boundary(start_byte) {
+        start_byte -= 1;
+    }
+    let suffix = old_bytes[start_byte..].iter().rev().zip(new_bytes[start_byte..].iter().rev())
+        .take_while(|(old, new)| old == new).count();
+    let (mut old_end_byte, mut new_end_byte) = (old_bytes.len() - suffix, new_bytes.len() - suffix);
+    while !old.is_char_boundary(old_end_byte) || !new.is_char_boundary(new_end_byte) {
+        old_end_byte += 1;
+        new_end_byte += 1;
+    }
+    Some(InputEdit {
+        start_byte,
+        old_end_byte,
+        new_end_byte,
+        start_position: point_at(&old, start_byte),
+        old_end_position: point_at(&old, old_end_byte),
+        new_end_position: point_at(&new, new_end_byte),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HtmlAnalyzer;
+    use ropey::Rope;
+
+    #[test]
+    fn block_syntax_is_masked() {
+        let masked = mask_blocks("@if (a < b) {\n<p>{{ a }}</p>\n} @else {<p></p>}");
+        assert_eq!(masked, "             \n<p>{{ a }}</p>\n         <p></p> ");
+    }
+
+    #[test]
+    fn unchanged_masked_text_needs_no_edit() {
+        // The braces of an interpolation don't close any block
+        assert!(masked_edit("<p>{{ a }}</p>", "<p>{{ a }}</p>").is_none());
+    }
+
+    #[test]
+    fn masking_away_from_the_edit_is_part_of_it() {
+        let old = "<div>if (a) {<b></b>}</div>";
+        let new = "<div>@if (a) {<b></b>}</div>";
+        let edit = masked_edit(old, new).unwrap();
+        // From the typed `@` to the closing brace the header now masks
+        assert_eq!((edit.start_byte, edit.old_end_byte, edit.new_end_byte), (5, 21, 22));
+        assert_eq!(edit.new_end_position, Point { row: 0, column: 22 });
+    }
+
+    #[test]
+    fn incremental_parses_match_full_parses() {
+        let mut analyzer = HtmlAnalyzer::new();
+        let edits = [
+            ("<div>if (a) {<b></b>}</div>", "<div>@if (a) {<b></b>}</div>"),
+            ("@if (a) {\n<b></b>\n}\n<i></i>", "if (a) {\n<b></b>\n}\n<i></i>"),
+            ("<p>é</p>\n@for (x of xs) {<li></li>}", "<p>éé</p>\n@for (x of xs) {<li></li>}"),
+        ];
+        for (old, new) in edits {
+            let mut tree = analyzer.parse(&Rope::from_str(old), None);
+            if let Some(edit) = masked_edit(old, new) {
+                tree.edit(&edit);
+            }
+            let incremental = analyzer.parse(&Rope::from_str(new), Some(&tree));
+            let full = analyzer.parse(&Rope::from_str(new), None);
+            assert_eq!(incremental.root_node().to_sexp(), full.root_node().to_sexp(), "{}", new);
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::completion::BUILTIN_ATTRIBUTE_DIRECTIVES;
+use crate::imports::ImportScope;
+use crate::index::{element_name, ComponentIndex};
+use crate::{bindable_components, node_text, parse_binding, to_range, walk_tree, BindingKind, Component, ComponentKind, Property};
+
+/// Code of the diagnostics that the import quick fix applies to.
+pub const MISSING_IMPORT: &str = "missing-import";
+
+/// Standard and Angular built-in elements whose name contains a dash and
+/// thus would otherwise be mistaken for a custom component.
+static BUILTIN_DASHED_TAGS: &[&str] = &[
+    "annotation-xml",
+    "color-profile",
+    "font-face",
+    "font-face-format",
+    "font-face-name",
+    "font-face-src",
+    "font-face-uri",
+    "missing-glyph",
+    "ng-container",
+    "ng-content",
+    "ng-template",
+];
+
+/// Native element properties that can be bound on any element, components included.
+static DOM_PROPERTIES: &[&str] = &[
+    "class", "dir", "disabled", "draggable", "hidden", "id", "innerHTML", "innerText", "lang",
+    "role", "style", "tabIndex", "tabindex", "textContent", "title",
+];
+
+/// Native DOM events that can be listened to on any element, components included.
+static DOM_EVENTS: &[&str] = &[
+    "blur", "change", "click", "contextmenu", "dblclick", "drag", "dragend", "dragenter",
+    "dragleave", "dragover", "dragstart", "drop", "focus", "focusin", "focusout", "input",
+    "keydown", "keypress", "keyup", "mousedown", "mouseenter", "mouseleave", "mousemove",
+    "mouseout", "mouseover", "mouseup", "scroll", "submit", "touchend", "touchmove",
+    "touchstart", "wheel",
+];
+
+fn warning(range: Range, message: String) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::WARNING),
+        source: Some(String::from("angular-lsp")),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Flags custom (dashed) element tags that match no known component selector,
+/// only those starting with one of `prefixes` when some are given.
+fn unknown_tags(root: &Node, rope: &Rope, components: &ComponentIndex, prefixes: &[String]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    walk_tree(root, &mut |node| {
+        let in_start_tag = node.parent()
+            .is_some_and(|parent| parent.kind() == "start_tag" || parent.kind() == "self_closing_tag");
+        if node.kind() != "tag_name" || !in_start_tag {
+            return;
+        }
+        let Some(tag_name) = node_text(&node, rope) else {
+            return;
+        };
+        // Other web components are none of the workspace's business
+        let name = element_name(&tag_name);
+        let prefixed = prefixes.is_empty() || prefixes.iter().any(|prefix| name.starts_with(prefix.as_str()));
+        if name.contains('-') && prefixed && !BUILTIN_DASHED_TAGS.contains(&name) && !components.contains_key(name) {
+            diagnostics.push(warning(to_range(rope, node.range()), format!("Unknown component '{}'", tag_name)));
+        }
+    });
+    diagnostics
+}
+
+/// Flags known components that the owner of the template doesn't import.
+fn unimported_components(root: &Node, rope: &Rope, components: &ComponentIndex, scope: &ImportScope) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    walk_tree(root, &mut |node| {
+        let in_start_tag = node.parent()
+            .is_some_and(|parent| parent.kind() == "start_tag" || parent.kind() == "self_closing_tag");
+        if node.kind() != "tag_name" || !in_start_tag {
+            return;
+        }
+        let Some(component) = node_text(&node, rope)
+            .and_then(|tag_name| components.get(&tag_name))
+            .filter(|component| component.kind == ComponentKind::Component) else {
+            return;
+        };
+        if !scope.provides(&component, components) {
+            diagnostics.push(Diagnostic {
+                code: Some(NumberOrString::String(String::from(MISSING_IMPORT))),
+                data: Some(serde_json::Value::String(component.selector.clone())),
+                ..warning(to_range(rope, node.range()), format!("Component '{}' is not imported", component.selector))
+            });
+        }
+    });
+    diagnostics
+}
+
+fn declares(inputs: &[Property], outputs: &[Property], name: &str, kind: BindingKind) -> bool {
+    match kind {
+        BindingKind::Property | BindingKind::TwoWay => inputs.iter().any(|input| input.binding_name() == name),
+        BindingKind::Event => outputs.iter().any(|output| output.binding_name() == name),
+    }
+}
+
+fn is_dom_binding(name: &str, kind: BindingKind) -> bool {
+    match kind {
+        BindingKind::Property => DOM_PROPERTIES.contains(&name)
+            || ["attr.", "class.", "style."].iter().any(|prefix| name.starts_with(prefix)),
+        BindingKind::Event => DOM_EVENTS.contains(&name.split('.').next().unwrap_or(name)),
+        // `[(x)]` binds both `[x]` and `(xChange)`
+        BindingKind::TwoWay => is_dom_binding(name, BindingKind::Property)
+            && is_dom_binding(&format!("{}Change", name), BindingKind::Event),
+    }
+}
+
+/// Whether a built-in attribute directive, like `ngModel`, takes the binding.
+fn is_builtin_directive_binding(name: &str, kind: BindingKind) -> bool {
+    let has_input = |name: &str| BUILTIN_ATTRIBUTE_DIRECTIVES.iter().any(|directive| directive.inputs.contains(&name));
+    let has_output = |name: &str| BUILTIN_ATTRIBUTE_DIRECTIVES.iter().any(|directive| directive.outputs.contains(&name));
+    match kind {
+        BindingKind::Property => has_input(name),
+        BindingKind::Event => has_output(name),
+        BindingKind::TwoWay => has_input(name) && has_output(&format!("{}Change", name)),
+    }
+}
+
+/// Flags `[input]`, `(output)` and `[(model)]` bindings that a known component doesn't declare,
+/// nor any attribute directive applying to its element.
+fn unknown_bindings(root: &Node, rope: &Rope, components: &ComponentIndex) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.kind() != "start_tag" && node.kind() != "self_closing_tag" {
+            return;
+        }
+        let Some(component) = node.named_child(0)
+            .and_then(|tag_name| node_text(&tag_name, rope))
+            .and_then(|tag_name| components.get(&tag_name))
+            .filter(|component| component.kind == ComponentKind::Component) else {
+            return;
+        };
+        let bindable = bindable_components(&node, rope, components);
+        let mut cursor = node.walk();
+        let attribute_names = node.named_children(&mut cursor)
+            .filter(|attribute| attribute.kind() == "attribute")
+            .filter_map(|attribute| attribute.named_child(0));
+        for attribute_name in attribute_names {
+            let Some(attribute) = node_text(&attribute_name, rope) else {
+                continue;
+            };
+            let Some((kind, name)) = parse_binding(&attribute) else {
+                continue;
+            };
+            if is_dom_binding(name, kind)
+                || is_builtin_directive_binding(name, kind)
+                || bindable.iter().any(|bindable| declares(&bindable.inputs, &bindable.outputs, name, kind)) {
+                continue;
+            }
+            let message = match kind {
+                BindingKind::Event => format!("Unknown output '{}' on component '{}'", name, component.selector),
+                _ => format!("Unknown input '{}' on component '{}'", name, component.selector),
+            };
+            diagnostics.push(warning(to_range(rope, attribute_name.range()), message));
+        }
+    });
+    diagnostics
+}
+
+/// Flags known components missing a binding of one of their required inputs.
+fn missing_required_inputs(root: &Node, rope: &Rope, components: &ComponentIndex) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.kind() != "start_tag" && node.kind() != "self_closing_tag" {
+            return;
+        }
+        let Some(tag_name) = node.named_child(0) else {
+            return;
+        };
+        let Some(component) = node_text(&tag_name, rope)
+            .and_then(|tag_name| components.get(&tag_name))
+            .filter(|component| component.kind == ComponentKind::Component) else {
+            return;
+        };
+        // A plain attribute binds the input to a static string
+        let mut cursor = node.walk();
+        let bound: Vec<String> = node.named_children(&mut cursor)
+            .filter(|attribute| attribute.kind() == "attribute")
+            .filter_map(|attribute| attribute.named_child(0))
+            .filter_map(|attribute_name| node_text(&attribute_name, rope))
+            .map(|attribute| match parse_binding(&attribute) {
+                Some((_, name)) => String::from(name),
+                None => attribute,
+            })
+            .collect();
+        for input in component.inputs.iter().filter(|input| input.required) {
+            if !bound.iter().any(|name| name == input.binding_name()) {
+                diagnostics.push(Diagnostic {
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    ..warning(to_range(rope, tag_name.range()),
+                        format!("Required input '{}' of component '{}' is not bound", input.binding_name(), component.selector))
+                });
+            }
+        }
+    });
+    diagnostics
+}
+
+/// Flags the selectors declared in `file_url` that other components declare too.
+pub fn duplicate_selectors(file_url: &Url, components: &ComponentIndex) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for selector in components.file_selectors(file_url) {
+        let declarations = components.declarations(&selector);
+        if declarations.len() < 2 {
+            continue;
+        }
+        for component in declarations.iter().filter(|component| component.file_url == *file_url) {
+            let others: Vec<&Component> = declarations.iter()
+                .filter(|other| other.class_name_range != component.class_name_range || other.file_url != component.file_url)
+                .collect();
+            let names: Vec<&str> = others.iter().map(|other| other.class_name.as_str()).collect();
+            diagnostics.push(Diagnostic {
+                related_information: Some(others.iter()
+                    .map(|other| DiagnosticRelatedInformation {
+                        location: Location::new(other.file_url.clone(), other.selector_range),
+                        message: format!("'{}' also declared by {}", selector, other.class_name),
+                    })
+                    .collect()),
+                ..warning(component.selector_range, format!("Selector '{}' is also declared by {}", selector, names.join(", ")))
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Method called by an event handler made of a single call, like `save()` or `select(item, $event);`.
+fn called_method(handler: &str) -> Option<&str> {
+    let handler = handler.trim().trim_end_matches(';').trim_end();
+    let name_end = handler.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))?;
+    let (name, call) = handler.split_at(name_end);
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit() || c == '$') || !call.starts_with('(') {
+        return None;
+    }
+    // The parenthesis opening the arguments must be the one closing the handler
+    let mut depth = 0;
+    for (index, c) in call.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 && index + 1 != call.len() {
+            return None;
+        }
+    }
+    (depth == 0).then_some(name)
+}
+
+/// Flags `(event)="method()"` handlers of the template of `owner` calling a method its class doesn't declare.
+fn unknown_handler_methods(root: &Node, rope: &Rope, owner: &Component) -> Vec<Diagnostic> {
+    // Inherited methods may be anything
+    if owner.super_class.is_some() {
+        return Vec::new();
+    }
+    let mut diagnostics = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.kind() != "attribute" {
+            return;
+        }
+        let is_event = node.named_child(0)
+            .and_then(|attribute_name| node_text(&attribute_name, rope))
+            .is_some_and(|attribute| matches!(parse_binding(&attribute), Some((BindingKind::Event, _))));
+        let value = node.named_child(1)
+            .map(|value| if value.kind() == "quoted_attribute_value" { value.named_child(0) } else { Some(value) });
+        let (true, Some(Some(value))) = (is_event, value) else {
+            return;
+        };
+        let Some(handler) = node_text(&value, rope) else {
+            return;
+        };
+        if let Some(method) = called_method(&handler).filter(|method| !owner.methods.iter().any(|known| known == method)) {
+            diagnostics.push(warning(to_range(rope, value.range()),
+                format!("Unknown method '{}' of component '{}'", method, owner.class_name)));
+        }
+    });
+    diagnostics
+}
+
+/// Whether `offset` is inside an interpolation, whose `<` comparisons the HTML grammar takes for tags.
+fn in_interpolation(offset: usize, rope: &Rope) -> bool {
+    let Some(before) = rope.get_byte_slice(..offset).map(String::from) else {
+        return false;
+    };
+    before.rfind("{{").is_some_and(|open| before.rfind("}}").is_none_or(|close| close < open))
+}
+
+/// Reports the nodes that the HTML grammar had to make up or skip, the outermost error only.
+fn syntax_errors(root: &Node, rope: &Rope) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    walk_tree(root, &mut |node| {
+        if !node.is_error() && !node.is_missing() {
+            return;
+        }
+        let mut ancestor = node.parent();
+        while let Some(parent) = ancestor {
+            if parent.is_error() {
+                return;
+            }
+            ancestor = parent.parent();
+        }
+        let start = if node.is_missing() { node.parent().unwrap_or(node) } else { node }.start_byte();
+        if in_interpolation(start, rope) {
+            return;
+        }
+        let mut cursor = node.walk();
+        let unclosed: Vec<String> = node.named_children(&mut cursor)
+            .filter(|child| node.is_error() && child.kind() == "start_tag")
+            .filter_map(|start_tag| start_tag.named_child(0))
+            .filter_map(|tag_name| node_text(&tag_name, rope))
+            .map(|tag_name| format!("<{}>", tag_name))
+            .collect();
+        let message = if !unclosed.is_empty() {
+            format!("Missing closing tag for {}", unclosed.join(", "))
+        } else if node.is_missing() {
+            let tag_name = node.parent()
+                .and_then(|element| element.named_child(0))
+                .and_then(|start_tag| start_tag.named_child(0))
+                .and_then(|tag_name| node_text(&tag_name, rope));
+            match (node.kind(), tag_name) {
+                ("end_tag", Some(tag_name)) => format!("Missing closing tag </{}>", tag_name),
+                (kind, _) if node.is_named() => format!("Missing {}", kind),
+                (token, _) => format!("Missing '{}'", token),
+            }
+        } else {
+            match node_text(&node, rope).map(|text| String::from(text.trim())) {
+                Some(text) if !text.is_empty() && text.chars().count() <= 20 => format!("Unexpected '{}'", text),
+                _ => String::from("Unexpected token"),
+            }
+        };
+        diagnostics.push(Diagnostic {
+            severity: Some(DiagnosticSeverity::ERROR),
+            ..warning(to_range(rope, node.range()), message)
+        });
+    });
+    diagnostics
+}
+
+/// Diagnostics of a template, `owner` being the component it belongs to when known,
+/// `prefixes` those of the selectors of the workspace.
+pub fn diagnostics(root: &Node, rope: &Rope, components: &ComponentIndex, owner: Option<&Component>, prefixes: &[String]) -> Vec<Diagnostic> {
+    let mut diagnostics = syntax_errors(root, rope);
+    diagnostics.append(&mut unknown_tags(root, rope, components, prefixes));
+    diagnostics.append(&mut unknown_bindings(root, rope, components));
+    diagnostics.append(&mut missing_required_inputs(root, rope, components));
+    if let Some(owner) = owner {
+        diagnostics.append(&mut unknown_handler_methods(root, rope, owner));
+    }
+    if let Some(scope) = owner.and_then(|owner| ImportScope::of(owner, components)) {
+        diagnostics.append(&mut unimported_components(root, rope, components, &scope));
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{file_url, index, template};
+
+    const CARD: &str = r#"
+@Component({ selector: 'app-card', template: '' })
+export class CardComponent {
+  @Input() title: string;
+  @Output() closed = new EventEmitter<void>();
+}"#;
+
+    fn messages(text: &str, components: &ComponentIndex) -> Vec<String> {
+        let (rope, tree) = template(text);
+        diagnostics(&tree.root_node(), &rope, components, None, &[]).into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn builtin_directive_bindings_are_not_flagged() {
+        let components = index(&[("card.component.ts", CARD)]);
+        let text = r#"<app-card [title]="t" [ngClass]="c" [ngStyle]="s" [routerLink]="['/']" [queryParams]="q"
+            [(ngModel)]="v" (ngModelChange)="changed()" (isActiveChange)="active()"></app-card>"#;
+        assert_eq!(messages(text, &components), Vec::<String>::new());
+    }
+
+    #[test]
+    fn undeclared_bindings_are_flagged() {
+        let components = index(&[("card.component.ts", CARD)]);
+        let text = r#"<app-card [titel]="t" (close)="c()" [(ngModelOptions)]="o"></app-card>"#;
+        assert_eq!(messages(text, &components), [
+            "Unknown input 'titel' on component 'app-card'",
+            "Unknown output 'close' on component 'app-card'",
+            // `ngModel` has no `ngModelOptionsChange` output
+            "Unknown input 'ngModelOptions' on component 'app-card'",
+        ]);
+    }
+
+    #[test]
+    fn directive_bindings_need_the_directive_on_the_element() {
+        let components = index(&[
+            ("card.component.ts", CARD),
+            ("tooltip.directive.ts", "@Directive({ selector: '[appTooltip]' })\nexport class TooltipDirective {\n  @Input() tooltipText: string;\n  @Output() shown = new EventEmitter<void>();\n}"),
+        ]);
+        assert_eq!(messages(r#"<app-card appTooltip [tooltipText]="t" (shown)="s()"></app-card>"#, &components), Vec::<String>::new());
+        assert_eq!(messages(r#"<app-card [tooltipText]="t" (shown)="s()"></app-card>"#, &components), [
+            "Unknown input 'tooltipText' on component 'app-card'",
+            "Unknown output 'shown' on component 'app-card'",
+        ]);
+    }
+
+    #[test]
+    fn duplicate_selectors_are_flagged_in_both_files() {
+        let components = index(&[
+            ("first.component.ts", "@Component({ selector: 'app-dup', template: '' })\nexport class FirstComponent {}"),
+            ("second.component.ts", "@Component({ selector: 'app-dup', template: '' })\nexport class SecondComponent {}"),
+        ]);
+        let first = duplicate_selectors(&file_url("first.component.ts"), &components);
+        let second = duplicate_selectors(&file_url("second.component.ts"), &components);
+        assert_eq!(first.iter().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<_>>(),
+            ["Selector 'app-dup' is also declared by SecondComponent"]);
+        assert_eq!(second.iter().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<_>>(),
+            ["Selector 'app-dup' is also declared by FirstComponent"]);
+        assert_eq!(first[0].severity, Some(DiagnosticSeverity::WARNING));
+        let related = first[0].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, file_url("second.component.ts"));
+        assert_eq!(components.declarations("app-dup").len(), 2);
+    }
+
+    #[test]
+    fn ng_templates_are_not_unknown_components() {
+        let text = r#"<ng-template #row let-item [ngTemplateOutlet]="row" [ngTemplateOutletContext]="{ $implicit: item }"></ng-template>"#;
+        assert_eq!(messages(text, &ComponentIndex::default()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn only_prefixed_unknown_elements_are_flagged_with_prefixes() {
+        let (rope, tree) = template("<my-web-component></my-web-component><app-missing></app-missing><svg:app-icon></svg:app-icon>");
+        let messages = |prefixes: &[String]| -> Vec<String> {
+            diagnostics(&tree.root_node(), &rope, &ComponentIndex::default(), None, prefixes).into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect()
+        };
+        assert_eq!(messages(&[String::from("app-")]), ["Unknown component 'app-missing'", "Unknown component 'svg:app-icon'"]);
+        // Without prefixes any unknown dashed element is
+        assert_eq!(messages(&[]).len(), 3);
+    }
+
+    #[test]
+    fn unclosed_elements_are_syntax_errors() {
+        let (rope, tree) = template("<div><span>");
+        let errors: Vec<_> = diagnostics(&tree.root_node(), &rope, &ComponentIndex::default(), None, &[]).into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message))
+            .collect();
+        assert_eq!(errors, [(Some(DiagnosticSeverity::ERROR), String::from("Missing closing tag for <div>, <span>"))]);
+        // Comparisons in interpolations aren't tags
+        assert_eq!(messages("<p>{{ a < b }}</p>", &ComponentIndex::default()), Vec::<String>::new());
+    }
+}
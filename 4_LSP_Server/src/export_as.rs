@@ -0,0 +1,83 @@
+//! Template reference variables set to a directive, like `#tooltip="appTooltip"`,
+//! through the names it gives with `exportAs`.
+
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::index::ComponentIndex;
+use crate::{bindable_components, find_node, node_text, Component};
+
+/// Names `component` exports, `exportAs` taking a comma separated list.
+pub fn export_names(component: &Component) -> impl Iterator<Item = &str> {
+    component.export_as.iter()
+        .flat_map(|names| names.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// Quoted value of the `#name="..."` reference variable under `offset`, inside its quotes.
+fn reference_value<'a>(start_tag: &Node<'a>, offset: usize, rope: &Rope) -> Option<Node<'a>> {
+    let quoted = find_node(start_tag, offset, vec![ "quoted_attribute_value" ])?;
+    if offset <= quoted.start_byte() || offset >= quoted.end_byte() {
+        return None;
+    }
+    let attribute_name = node_text(&quoted.parent()?.named_child(0)?, rope)?;
+    attribute_name.starts_with('#').then_some(quoted)
+}
+
+/// The directive whose exported name is the value of the reference variable under `offset`,
+/// preferring those applying to the element.
+pub fn exported_at(root: &Node, offset: usize, rope: &Rope, components: &ComponentIndex) -> Option<Component> {
+    let quoted = reference_value(root, offset, rope)?;
+    let start_tag = quoted.parent()?.parent()?;
+    let name = node_text(&quoted, rope)?;
+    let name = name.trim_matches(|c| c == '"' || c == '\'');
+    let exports = |component: &Component| export_names(component).any(|exported| exported == name);
+    bindable_components(&start_tag, rope, components).into_iter()
+        .find(|component| exports(component))
+        .or_else(|| components.iter().find(|component| exports(component)).map(|component| component.clone()))
+}
+
+/// Completions of the names exported by the directives of the element,
+/// when `offset` is in the value of one of its reference variables.
+pub fn completions(start_tag: &Node, offset: usize, rope: &Rope, components: &ComponentIndex) -> Option<Vec<CompletionItem>> {
+    reference_value(start_tag, offset, rope)?;
+    let completions = bindable_components(start_tag, rope, components).iter()
+        .flat_map(|component| export_names(component).map(|name| CompletionItem {
+            label: String::from(name),
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some(component.class_name.clone()),
+            ..Default::default()
+        }).collect::<Vec<_>>())
+        .collect();
+    Some(completions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{index, template};
+
+    const TOOLTIP: &str = r#"
+@Directive({ selector: '[appTooltip]', exportAs: 'appTooltip, tooltip' })
+export class TooltipDirective {}"#;
+
+    #[test]
+    fn reference_variables_resolve_to_exporting_directives() {
+        let components = index(&[("tooltip.directive.ts", TOOLTIP)]);
+        let text = "<button appTooltip #tooltip=\"appTooltip\"></button>";
+        let (rope, tree) = template(text);
+        let offset = text.find("=\"appTooltip").unwrap() + 3;
+        let directive = exported_at(&tree.root_node(), offset, &rope, &components).unwrap();
+        assert_eq!(directive.class_name, "TooltipDirective");
+        // The name of the variable isn't its value
+        assert!(exported_at(&tree.root_node(), text.find("#tooltip").unwrap() + 2, &rope, &components).is_none());
+
+        let start_tag = tree.root_node().named_child(0).unwrap().named_child(0).unwrap();
+        let labels: Vec<String> = completions(&start_tag, offset, &rope, &components).unwrap().into_iter()
+            .map(|item| item.label)
+            .collect();
+        assert_eq!(labels, ["appTooltip", "tooltip"]);
+    }
+}
@@ -0,0 +1,53 @@
+use std::ops::Range;
+
+use ropey::Rope;
+use tree_sitter::Node;
+
+use crate::{find_node, node_text, parse_binding};
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Start byte of the Angular expression around `offset`, which the HTML grammar
+/// only sees as text: an interpolation `{{ }}` or the value of a binding.
+/// `text` is the content of `node` and `before` its part up to `offset`.
+fn expression_start(node: &Node, text: &str, before: &str, rope: &Rope) -> Option<usize> {
+    if let Some(open) = before.rfind("{{") {
+        if !before[open..].contains("}}") {
+            return Some(open + 2);
+        }
+    }
+    let attribute_name = node.parent()
+        .filter(|parent| parent.kind() == "quoted_attribute_value")
+        .and_then(|quoted| quoted.parent())
+        .or_else(|| node.parent())
+        .filter(|attribute| attribute.kind() == "attribute")
+        .and_then(|attribute| attribute.named_child(0))
+        .and_then(|attribute_name| node_text(&attribute_name, rope))?;
+    let is_binding = parse_binding(&attribute_name).is_some() || attribute_name.starts_with('*');
+    (node.kind() == "attribute_value" && is_binding && !text.contains("{{")).then_some(0)
+}
+
+/// Name of the pipe under `offset`, possibly empty when only `|` was typed,
+/// along with its byte range in the document.
+pub fn pipe_at(root: &Node, offset: usize, rope: &Rope) -> Option<(String, Range<usize>)> {
+    let node = find_node(root, offset, vec!["text", "attribute_value"])?;
+    let text = node_text(&node, rope)?;
+    let before = text.get(..offset - node.start_byte())?;
+    let start = expression_start(&node, &text, before, rope)?;
+
+    let bar = start + before.get(start..)?.rfind('|')?;
+    if before[..bar].ends_with('|') {
+        return None;
+    }
+    let after_bar = &before[bar + 1..];
+    let name_start = bar + 1 + (after_bar.len() - after_bar.trim_start().len());
+    if !before[name_start..].chars().all(is_identifier_char) {
+        return None;
+    }
+    let name_end = text[name_start..].find(|c| !is_identifier_char(c))
+        .map_or(text.len(), |end| name_start + end);
+    let name = String::from(&text[name_start..name_end]);
+    Some((name, node.start_byte() + name_start..node.start_byte() + name_end))
+}
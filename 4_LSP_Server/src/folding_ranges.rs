@@ -0,0 +1,36 @@
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::walk_tree;
+
+/// Folds of the elements and comments of a template spanning several lines.
+pub fn folding_ranges(root: &Node) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+    walk_tree(root, &mut |node| {
+        let (start_line, end_line, kind) = match node.kind() {
+            "element" => {
+                // Void elements have no end tag, only their start tag may span lines
+                let last_tag = node.named_child(node.named_child_count().saturating_sub(1))
+                    .filter(|end_tag| end_tag.kind() == "end_tag")
+                    .or_else(|| node.named_child(0));
+                let end_line = last_tag.map_or(node.end_position().row, |tag| match tag.kind() {
+                    "end_tag" => tag.start_position().row,
+                    _ => tag.end_position().row,
+                });
+                (node.start_position().row, end_line, None)
+            }
+            "comment" => (node.start_position().row, node.end_position().row, Some(FoldingRangeKind::Comment)),
+            _ => return,
+        };
+        if end_line > start_line {
+            ranges.push(FoldingRange {
+                start_line: start_line as u32,
+                start_character: None,
+                end_line: end_line as u32,
+                end_character: None,
+                kind,
+            });
+        }
+    });
+    ranges
+}
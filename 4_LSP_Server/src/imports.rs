@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+use tree_sitter::Parser;
+
+use crate::index::ComponentIndex;
+use crate::{to_position, ClassList, Component, NgModule};
+
+/// Where the classes used by a template come from: the `imports` of its
+/// standalone component, or the `NgModule` declaring its component.
+pub enum ImportScope {
+    Standalone(Box<Component>),
+    Module(Box<NgModule>),
+}
+
+impl ImportScope {
+    pub fn of(owner: &Component, components: &ComponentIndex) -> Option<ImportScope> {
+        if owner.standalone {
+            return Some(ImportScope::Standalone(Box::new(owner.clone())));
+        }
+        components.declaring_module(&owner.class_name).map(|module| ImportScope::Module(Box::new(module)))
+    }
+
+    pub fn file_url(&self) -> &Url {
+        match self {
+            ImportScope::Standalone(component) => &component.file_url,
+            ImportScope::Module(module) => &module.file_url,
+        }
+    }
+
+    /// Whether `component` can be used in the templates of this scope.
+    pub fn provides(&self, component: &Component, components: &ComponentIndex) -> bool {
+        let (local, imports) = match self {
+            ImportScope::Standalone(owner) => (vec![&owner.class_name], owner.imports.as_ref()),
+            ImportScope::Module(module) => (names(module.declarations.as_ref()).collect(), module.imports.as_ref()),
+        };
+        local.contains(&&component.class_name) || names(imports).any(|import| {
+            import == &component.class_name || components.module(import)
+                .is_some_and(|module| names(module.exports.as_ref()).any(|export| export == &component.class_name))
+        })
+    }
+
+    /// Import of `component` into this scope: the TypeScript import along with
+    /// the class added to the `imports` (or `declarations`) of the decorator.
+    pub fn import_edit(&self, component: &Component, contents: &str, components: &ComponentIndex) -> Option<(String, WorkspaceEdit)> {
+        // A component declared by a module is only available through that module
+        let (class_name, class_url) = match components.declaring_module(&component.class_name) {
+            Some(module) if !component.standalone => (module.class_name, module.file_url),
+            _ => (component.class_name.clone(), component.file_url.clone()),
+        };
+        let (property, list, metadata_start) = match self {
+            ImportScope::Standalone(owner) => ("imports", owner.imports.as_ref(), owner.metadata_start),
+            ImportScope::Module(module) if class_name == component.class_name && !component.standalone =>
+                ("declarations", module.declarations.as_ref(), module.metadata_start),
+            ImportScope::Module(module) => ("imports", module.imports.as_ref(), module.metadata_start),
+        };
+        let class_edit = match list {
+            Some(list) if list.names.is_empty() => TextEdit::new(Range::new(list.insert_position, list.insert_position), class_name.clone()),
+            Some(list) => TextEdit::new(Range::new(list.insert_position, list.insert_position), format!(", {}", class_name)),
+            None => TextEdit::new(Range::new(metadata_start, metadata_start), format!("\n  {}: [{}],", property, class_name)),
+        };
+
+        let mut edits = vec![class_edit];
+        if self.file_url() != &class_url {
+            let module = module_specifier(self.file_url(), &class_url)?;
+            let import = format!("import {{ {} }} from '{}';", class_name, module);
+            edits.insert(0, match imports_end(contents) {
+                Some(position) => TextEdit::new(Range::new(position, position), format!("\n{}", import)),
+                None => TextEdit::new(Range::default(), format!("{}\n", import)),
+            });
+        }
+        let mut changes = HashMap::new();
+        changes.insert(self.file_url().clone(), edits);
+        Some((class_name, WorkspaceEdit::new(changes)))
+    }
+}
+
+fn names(list: Option<&ClassList>) -> impl Iterator<Item = &String> {
+    list.into_iter().flat_map(|list| list.names.iter())
+}
+
+/// End of the last import statement of a TypeScript source.
+fn imports_end(contents: &str) -> Option<Position> {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_typescript::language_typescript()).ok()?;
+    let tree = parser.parse(contents, None)?;
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let last_import = root.named_children(&mut cursor)
+        .filter(|node| node.kind() == "import_statement")
+        .last();
+    last_import.map(|import| to_position(contents, import.end_byte(), import.end_position()))
+}
+
+/// Relative module specifier of `to` when imported from `from`.
+fn module_specifier(from: &Url, to: &Url) -> Option<String> {
+    let relative = from.make_relative(to)?;
+    // ES modules import `.mts` and `.cts` sources by their compiled extension
+    let module = match relative.rsplit_once('.') {
+        Some((module, "ts" | "tsx")) => String::from(module),
+        Some((module, "mts")) => format!("{}.mjs", module),
+        Some((module, "cts")) => format!("{}.cjs", module),
+        _ => relative.clone(),
+    };
+    if module.starts_with("../") {
+        Some(module)
+    } else {
+        Some(format!("./{}", module))
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::mapref::one::Ref;
+use dashmap::iter::Iter;
+use dashmap::DashMap;
+use serde::Serialize;
+use tower_lsp::lsp_types::Url;
+
+use crate::{Component, NgModule, Pipe};
+
+/// Summary of an indexed component, as answered to the `angular/componentIndex` request.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentEntry {
+    pub selector: String,
+    pub class_name: String,
+    pub file_url: Url,
+    pub template_url: Option<Url>,
+    /// Class name of the `NgModule` declaring a component that isn't standalone.
+    pub declaring_module: Option<String>,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+/// Name of an element without its namespace, like `app-icon` for `svg:app-icon` or `:svg:app-icon`,
+/// as Angular leaves namespaces out of selector matching.
+pub fn element_name(tag_name: &str) -> &str {
+    let name = tag_name.strip_prefix(':').unwrap_or(tag_name);
+    match name.split_once(':') {
+        Some((namespace, name)) if !name.is_empty() && namespace.chars().all(|c| c.is_ascii_alphabetic()) => name,
+        _ => tag_name,
+    }
+}
+
+/// Components of the workspace by selector, along with the selectors
+/// declared by each file so that a file can be re-indexed cleanly.
+#[derive(Default)]
+pub struct ComponentIndex {
+    components: DashMap<String, Component>,
+    /// Components declaring a selector that the one in `components` already declared.
+    duplicates: DashMap<String, Vec<Component>>,
+    /// Files last reported with a duplicate selector, whose warnings may need clearing.
+    reported_duplicates: Mutex<HashSet<Url>>,
+    files: DashMap<Url, Vec<String>>,
+    pipes: DashMap<String, Pipe>,
+    modules: DashMap<String, NgModule>,
+    /// Class name of the module declaring each component class.
+    declaring_modules: DashMap<String, String>,
+    /// File and class name of the component owning each external `templateUrl` file,
+    /// as duplicates share their selector.
+    template_owners: DashMap<Url, (Url, String)>,
+    /// Bumped on every change of the index.
+    revision: AtomicUsize,
+}
+
+impl ComponentIndex {
+    /// Component of `selector`, or of the tag name without its namespace.
+    /// Selectors match exactly, as Angular's do, surrounding whitespace aside.
+    pub fn get(&self, selector: &str) -> Option<Ref<'_, String, Component>> {
+        let selector = selector.trim();
+        self.components.get(selector).or_else(|| self.components.get(element_name(selector)))
+    }
+
+    /// Every component declaring `selector`, more than one being a conflict.
+    pub fn declarations(&self, selector: &str) -> Vec<Component> {
+        let selector = selector.trim();
+        let selector = if self.components.contains_key(selector) { selector } else { element_name(selector) };
+        let mut declarations: Vec<Component> = self.components.get(selector).map(|component| component.clone()).into_iter().collect();
+        if let Some(duplicates) = self.duplicates.get(selector) {
+            declarations.extend(duplicates.iter().cloned());
+        }
+        declarations
+    }
+
+    /// Selectors declared by the components of `file_url`.
+    pub fn file_selectors(&self, file_url: &Url) -> Vec<String> {
+        self.files.get(file_url).map(|selectors| selectors.clone()).unwrap_or_default()
+    }
+
+    /// Files with a duplicate selector, along with the ones that had one when last
+    /// asked, so that the warnings of both get published.
+    pub fn duplicate_files_to_report(&self) -> Vec<Url> {
+        let selectors: Vec<String> = self.duplicates.iter().map(|duplicates| duplicates.key().clone()).collect();
+        let current: HashSet<Url> = selectors.iter()
+            .flat_map(|selector| self.declarations(selector))
+            .map(|component| component.file_url)
+            .collect();
+        let mut reported = self.reported_duplicates.lock().unwrap();
+        let files = current.union(&reported).cloned().collect();
+        *reported = current;
+        files
+    }
+
+    pub fn contains_key(&self, selector: &str) -> bool {
+        let selector = selector.trim();
+        self.components.contains_key(selector) || self.components.contains_key(element_name(selector))
+    }
+
+    pub fn iter(&self) -> Iter<'_, String, Component> {
+        self.components.iter()
+    }
+
+    pub fn contains_file(&self, file_url: &Url) -> bool {
+        self.files.contains_key(file_url)
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn pipe(&self, name: &str) -> Option<Ref<'_, String, Pipe>> {
+        self.pipes.get(name)
+    }
+
+    pub fn pipes(&self) -> Iter<'_, String, Pipe> {
+        self.pipes.iter()
+    }
+
+    pub fn module(&self, class_name: &str) -> Option<Ref<'_, String, NgModule>> {
+        self.modules.get(class_name)
+    }
+
+    /// Module listing `class_name` in its declarations.
+    pub fn declaring_module(&self, class_name: &str) -> Option<NgModule> {
+        let module = self.declaring_modules.get(class_name)?.clone();
+        self.modules.get(&module).map(|module| module.clone())
+    }
+
+    /// Components, pipes and modules that `file_url` declares, as last given to `update_file`.
+    pub fn file_declarations(&self, file_url: &Url) -> (Vec<Component>, Vec<Pipe>, Vec<NgModule>) {
+        let mut selectors = self.file_selectors(file_url);
+        selectors.sort();
+        selectors.dedup();
+        let components = selectors.iter()
+            .flat_map(|selector| self.declarations(selector))
+            .filter(|component| component.file_url == *file_url)
+            .collect();
+        let pipes = self.pipes.iter()
+            .filter(|pipe| pipe.file_url == *file_url)
+            .map(|pipe| pipe.clone())
+            .collect();
+        let modules = self.modules.iter()
+            .filter(|module| module.file_url == *file_url)
+            .map(|module| module.clone())
+            .collect();
+        (components, pipes, modules)
+    }
+
+    /// Component whose `templateUrl` is the `template_url` document.
+    pub fn template_owner(&self, template_url: &Url) -> Option<Component> {
+        let (file_url, class_name) = self.template_owners.get(template_url)?.clone();
+        let is_owner = |component: &Component| component.file_url == file_url && component.class_name == class_name;
+        self.files.get(&file_url)?.iter().find_map(|selector| {
+            self.components.get(selector).filter(|component| is_owner(component)).map(|component| component.clone())
+                .or_else(|| self.duplicates.get(selector)?.iter().find(|component| is_owner(component)).cloned())
+        })
+    }
+
+    /// Every indexed component, sorted by selector.
+    pub fn entries(&self) -> Vec<ComponentEntry> {
+        let mut entries: Vec<ComponentEntry> = self.components.iter()
+            .map(|component| ComponentEntry {
+                selector: component.selector.clone(),
+                class_name: component.class_name.clone(),
+                file_url: component.file_url.clone(),
+                template_url: component.template_url.clone(),
+                declaring_module: self.declaring_modules.get(&component.class_name).map(|module| module.clone()),
+                inputs: component.inputs.iter().map(|input| input.binding_name().to_owned()).collect(),
+                outputs: component.outputs.iter().map(|output| output.binding_name().to_owned()).collect(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.selector.cmp(&b.selector));
+        entries
+    }
+
+    /// Revision of the index, which differs once anything changed in it.
+    pub fn revision(&self) -> usize {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    /// Replaces whatever `file_url` declared before with `components`, `pipes` and `modules`.
+    pub fn update_file(&self, file_url: &Url, components: Vec<Component>, pipes: Vec<Pipe>, modules: Vec<NgModule>) {
+        self.remove_file(file_url);
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        let selectors = components.iter().map(|component| component.selector.clone()).collect();
+        for component in components {
+            if let Some(template_url) = &component.template_url {
+                self.template_owners.insert(template_url.clone(), (file_url.clone(), component.class_name.clone()));
+            }
+            match self.components.entry(component.selector.clone()) {
+                Entry::Occupied(_) => self.duplicates.entry(component.selector.clone()).or_default().push(component),
+                Entry::Vacant(entry) => {
+                    entry.insert(component);
+                }
+            }
+        }
+        for pipe in pipes {
+            self.pipes.insert(pipe.name.clone(), pipe);
+        }
+        for module in modules {
+            for declaration in module.declarations.iter().flat_map(|declarations| declarations.names.iter()) {
+                self.declaring_modules.insert(declaration.clone(), module.class_name.clone());
+            }
+            self.modules.insert(module.class_name.clone(), module);
+        }
+        self.files.insert(file_url.clone(), selectors);
+    }
+
+    /// Removes the components of every file under the `folder` directory.
+    pub fn remove_folder(&self, folder: &Url) {
+        let Ok(folder) = folder.to_file_path() else {
+            return;
+        };
+        let file_urls: Vec<Url> = self.files.iter()
+            .map(|file| file.key().clone())
+            .filter(|file_url| file_url.to_file_path().is_ok_and(|path| path.starts_with(&folder)))
+            .collect();
+        for file_url in file_urls {
+            self.remove_file(&file_url);
+        }
+    }
+
+    pub fn clear(&self) {
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        self.components.clear();
+        self.duplicates.clear();
+        self.files.clear();
+        self.pipes.clear();
+        self.modules.clear();
+        self.declaring_modules.clear();
+        self.template_owners.clear();
+    }
+
+    pub fn remove_file(&self, file_url: &Url) {
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        self.pipes.retain(|_, pipe| pipe.file_url != *file_url);
+        let removed_modules: Vec<String> = self.modules.iter()
+            .filter(|module| module.file_url == *file_url)
+            .map(|module| module.class_name.clone())
+            .collect();
+        self.declaring_modules.retain(|_, module| !removed_modules.contains(module));
+        self.modules.retain(|_, module| module.file_url != *file_url);
+        self.template_owners.retain(|_, (owner_file_url, _)| owner_file_url != file_url);
+        if let Some((_, selectors)) = self.files.remove(file_url) {
+            for selector in selectors {
+                if let Some(mut duplicates) = self.duplicates.get_mut(&selector) {
+                    duplicates.retain(|component| component.file_url != *file_url);
+                }
+                // A duplicate takes the place of the removed component
+                if self.components.remove_if(&selector, |_, component| component.file_url == *file_url).is_some() {
+                    let replacement = self.duplicates.get_mut(&selector)
+                        .and_then(|mut duplicates| (!duplicates.is_empty()).then(|| duplicates.remove(0)));
+                    if let Some(replacement) = replacement {
+                        self.components.insert(selector.clone(), replacement);
+                    }
+                }
+                self.duplicates.remove_if(&selector, |_, duplicates| duplicates.is_empty());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::element_name;
+    use crate::testing::{file_url, index};
+
+    const SHARED_MODULE: &str = r#"
+@NgModule({
+  declarations: [CardComponent, BadgeComponent],
+  exports: [CardComponent],
+})
+export class SharedModule {}"#;
+
+    const CARD: &str = "@Component({ selector: 'app-card', template: '' })\nexport class CardComponent {}";
+
+    const BADGE: &str = "@Component({ selector: 'app-badge', template: '' })\nexport class BadgeComponent {}";
+
+    const ALERT: &str = "@Component({ selector: 'app-alert', standalone: true, template: '' })\nexport class AlertComponent {}";
+
+    #[test]
+    fn components_know_their_declaring_module() {
+        let index = index(&[
+            ("src/app/shared.module.ts", SHARED_MODULE),
+            ("src/app/card.component.ts", CARD),
+            ("src/app/badge.component.ts", BADGE),
+            ("src/app/alert.component.ts", ALERT),
+        ]);
+        let module = index.declaring_module("BadgeComponent").unwrap();
+        assert_eq!(module.class_name, "SharedModule");
+        assert_eq!(module.file_url, file_url("src/app/shared.module.ts"));
+        assert_eq!(module.exports.unwrap().names, vec!["CardComponent"]);
+        assert!(index.declaring_module("AlertComponent").is_none());
+        assert!(index.get("app-alert").unwrap().standalone);
+
+        let entries = index.entries();
+        let declaring_modules: Vec<(&str, Option<&str>)> = entries.iter()
+            .map(|entry| (entry.selector.as_str(), entry.declaring_module.as_deref()))
+            .collect();
+        assert_eq!(declaring_modules, vec![
+            ("app-alert", None),
+            ("app-badge", Some("SharedModule")),
+            ("app-card", Some("SharedModule")),
+        ]);
+    }
+
+    #[test]
+    fn removed_modules_declare_nothing() {
+        let index = index(&[("src/app/shared.module.ts", SHARED_MODULE), ("src/app/card.component.ts", CARD)]);
+        index.remove_file(&file_url("src/app/shared.module.ts"));
+        assert!(index.declaring_module("CardComponent").is_none());
+    }
+
+    #[test]
+    fn selectors_match_exactly_but_for_whitespace() {
+        let index = index(&[("src/app/my-comp-2.component.ts", "@Component({ selector: 'my-comp-2', template: '' })\nexport class MyComp2Component {}")]);
+        assert!(index.contains_key(" my-comp-2\n"));
+        assert_eq!(index.declarations("my-comp-2 ").len(), 1);
+        assert_eq!(index.get("svg:my-comp-2").unwrap().class_name, "MyComp2Component");
+        assert!(index.get("My-Comp-2").is_none());
+        assert!(!index.contains_key("my-comp"));
+    }
+
+    #[test]
+    fn element_names_leave_out_namespaces() {
+        assert_eq!(element_name("svg:app-icon"), "app-icon");
+        assert_eq!(element_name(":svg:app-icon"), "app-icon");
+        assert_eq!(element_name("app-icon"), "app-icon");
+        // Not namespaces
+        assert_eq!(element_name("svg:"), "svg:");
+        assert_eq!(element_name("x-1:app-icon"), "x-1:app-icon");
+    }
+
+    #[test]
+    fn template_owners_stay_apart_when_sharing_a_selector() {
+        let index = index(&[
+            ("src/app/first/card.component.ts", "@Component({ selector: 'app-card', templateUrl: './card.component.html' })\nexport class FirstCardComponent {}"),
+            ("src/app/second/card.component.ts", "@Component({ selector: 'app-card', templateUrl: './card.component.html' })\nexport class SecondCardComponent {}"),
+        ]);
+        let owner = |path: &str| index.template_owner(&file_url(path)).map(|owner| owner.class_name);
+        assert_eq!(owner("src/app/first/card.component.html").as_deref(), Some("FirstCardComponent"));
+        // The duplicate owns its own template
+        assert_eq!(owner("src/app/second/card.component.html").as_deref(), Some("SecondCardComponent"));
+
+        // Analyzing or removing a file leaves the owners of the other one alone
+        let (components, pipes, modules) = index.file_declarations(&file_url("src/app/first/card.component.ts"));
+        index.update_file(&file_url("src/app/first/card.component.ts"), components, pipes, modules);
+        assert_eq!(owner("src/app/second/card.component.html").as_deref(), Some("SecondCardComponent"));
+        index.remove_file(&file_url("src/app/first/card.component.ts"));
+        assert_eq!(owner("src/app/first/card.component.html"), None);
+        assert_eq!(owner("src/app/second/card.component.html").as_deref(), Some("SecondCardComponent"));
+    }
+}
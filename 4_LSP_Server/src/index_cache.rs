@@ -0,0 +1,144 @@
+//! Index saved in each workspace folder once indexed, so that the next start
+//! only analyzes the files modified since.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Url;
+
+use crate::index::ComponentIndex;
+use crate::{Component, NgModule, Pipe};
+
+/// Path of the cache in a workspace folder, in the cache folder of the Angular CLI that projects already ignore.
+pub const FILE_NAME: &str = ".angular/cache/angular-lsp-index.json";
+
+/// Bumped whenever the cached types change, so that caches of another format get discarded.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct IndexCache {
+    version: u32,
+    files: Vec<CachedFile>,
+}
+
+/// Read first, as a cache of another format may not read as an `IndexCache`.
+#[derive(Deserialize)]
+struct CacheVersion {
+    version: u32,
+}
+
+/// What a file declared, as of its modification time `modified`.
+#[derive(Serialize, Deserialize)]
+pub struct CachedFile {
+    path: PathBuf,
+    pub modified: SystemTime,
+    pub components: Vec<Component>,
+    pub pipes: Vec<Pipe>,
+    pub modules: Vec<NgModule>,
+}
+
+impl CachedFile {
+    /// Whether the file wasn't modified since it was cached.
+    pub fn is_fresh(&self) -> bool {
+        modified(&self.path) == Some(self.modified)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Cached files of the `root` workspace folder by path, none without a cache of the current format.
+pub fn load(root: &str) -> HashMap<PathBuf, CachedFile> {
+    let path = Path::new(root).join(FILE_NAME);
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return HashMap::new();
+    };
+    let cache = match serde_json::from_str::<CacheVersion>(&contents) {
+        Ok(CacheVersion { version: FORMAT_VERSION }) => serde_json::from_str::<IndexCache>(&contents),
+        Ok(CacheVersion { version }) => {
+            debug!("Discarding the index cache {:?} of format {}", path, version);
+            return HashMap::new();
+        }
+        Err(e) => Err(e),
+    };
+    match cache {
+        Ok(cache) => cache.files.into_iter()
+            .map(|file| (file.path.clone(), file))
+            .collect(),
+        Err(e) => {
+            warn!("Discarding the invalid index cache {:?} {:?}", path, e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Saves what the index knows of the `files` of the `root` workspace folder,
+/// each with its modification time when it was indexed.
+pub fn save(root: &str, files: &[(PathBuf, SystemTime)], components: &ComponentIndex) -> io::Result<()> {
+    let files = files.iter()
+        .filter(|(path, _)| path.starts_with(root))
+        .filter_map(|(path, modified)| {
+            let file_url = Url::from_file_path(path).ok()?;
+            let (components, pipes, modules) = components.file_declarations(&file_url);
+            Some(CachedFile { path: path.clone(), modified: *modified, components, pipes, modules })
+        })
+        .collect();
+    let path = Path::new(root).join(FILE_NAME);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string(&IndexCache { version: FORMAT_VERSION, files })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::folder;
+    use crate::ComponentAnalyzer;
+
+    const CARD: &str = "@Component({ selector: 'app-card', template: '' })\nexport class CardComponent {}";
+
+    #[test]
+    fn saved_files_load_back_until_modified() {
+        let root = folder(&[("src/app/card.component.ts", CARD)]);
+        let path = root.join("src/app/card.component.ts");
+        let components = ComponentIndex::default();
+        ComponentAnalyzer::new().unwrap().analyze_file(&path, &Default::default(), &components, u64::MAX).unwrap();
+        let root = root.to_str().unwrap();
+        save(root, &[(path.clone(), modified(&path).unwrap()), (PathBuf::from("/elsewhere/other.ts"), SystemTime::now())], &components).unwrap();
+
+        let cache = load(root);
+        assert_eq!(cache.len(), 1);
+        let cached = &cache[&path];
+        assert!(cached.is_fresh());
+        assert_eq!(cached.components.iter().map(|component| component.selector.as_str()).collect::<Vec<_>>(), ["app-card"]);
+
+        fs::File::options().write(true).open(&path).unwrap()
+            .set_modified(cached.modified + std::time::Duration::from_secs(1)).unwrap();
+        assert!(!cached.is_fresh());
+    }
+
+    #[test]
+    fn caches_of_another_format_are_discarded() {
+        let root = folder(&[("src/app/card.component.ts", CARD)]);
+        let root = root.to_str().unwrap();
+        save(root, &[], &ComponentIndex::default()).unwrap();
+        let cache_path = Path::new(root).join(FILE_NAME);
+        let contents = fs::read_to_string(&cache_path).unwrap();
+        // Files of another format may not even read as those of this one
+        let newer = contents.replace(&format!("\"version\":{}", FORMAT_VERSION), &format!("\"version\":{}", FORMAT_VERSION + 1))
+            .replace("\"files\":[]", "\"files\":[{ \"path\": 1 }]");
+        assert_ne!(newer, contents);
+        fs::write(&cache_path, &newer).unwrap();
+        assert!(load(root).is_empty());
+        fs::write(&cache_path, "{ \"version\": 1, \"files\": [").unwrap();
+        assert!(load(root).is_empty());
+        assert!(load("/nonexistent").is_empty());
+    }
+}
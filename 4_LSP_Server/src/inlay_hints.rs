@@ -0,0 +1,50 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::index::ComponentIndex;
+use crate::{node_text, parse_binding, to_position, walk_tree, BindingKind, ComponentKind};
+
+/// Declared type of each input bound on a known component, after the attribute name.
+pub fn input_type_hints(root: &Node, rope: &Rope, components: &ComponentIndex) -> Vec<InlayHint> {
+    let mut hints = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.kind() != "start_tag" && node.kind() != "self_closing_tag" {
+            return;
+        }
+        let Some(component) = node.named_child(0)
+            .and_then(|tag_name| node_text(&tag_name, rope))
+            .and_then(|tag_name| components.get(&tag_name))
+            .filter(|component| component.kind == ComponentKind::Component) else {
+            return;
+        };
+        let mut cursor = node.walk();
+        let attribute_names = node.named_children(&mut cursor)
+            .filter(|attribute| attribute.kind() == "attribute")
+            .filter_map(|attribute| attribute.named_child(0));
+        for attribute_name in attribute_names {
+            let Some(attribute) = node_text(&attribute_name, rope) else {
+                continue;
+            };
+            let Some((BindingKind::Property | BindingKind::TwoWay, name)) = parse_binding(&attribute) else {
+                continue;
+            };
+            let value_type = component.inputs.iter()
+                .find(|input| input.binding_name() == name)
+                .and_then(|input| input.value_type.clone());
+            if let Some(value_type) = value_type {
+                hints.push(InlayHint {
+                    position: to_position(rope, attribute_name.end_byte(), attribute_name.end_position()),
+                    label: InlayHintLabel::String(format!(": {}", value_type)),
+                    kind: Some(InlayHintKind::TYPE),
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: None,
+                    padding_right: None,
+                    data: None,
+                });
+            }
+        }
+    });
+    hints
+}
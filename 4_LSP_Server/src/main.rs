@@ -1,16 +1,66 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
-use std::sync::Arc;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use glob::glob;
 use log::{debug, warn, error};
-use tower_lsp::jsonrpc::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tower_lsp::jsonrpc::{Error, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
-use tree_sitter::{Parser, Query, QueryCursor, Tree, Node};
+use tree_sitter::{Language, Parser, Query, QueryCursor, QueryMatch, Tree, Node};
 use ropey::Rope;
 
+mod angular_json;
+mod binding_brackets;
+mod code_lens;
+mod completion;
+mod config;
+mod control_flow;
+mod diagnostics;
+mod export_as;
+mod expression;
+mod folding_ranges;
+mod imports;
+mod index;
+mod index_cache;
+mod inlay_hints;
+mod microsyntax;
+mod progress;
+mod property_rename;
+mod pull_diagnostics;
+mod save_formatting;
+mod selection_ranges;
+mod semantic_tokens;
+mod signature_help;
+mod style_classes;
+mod template_symbols;
+#[cfg(test)]
+mod testing;
+mod type_hierarchy;
+mod usages;
+
+use config::{Config, LogLevel, DEFAULT_SOURCE_ROOT};
+use imports::ImportScope;
+use index::{element_name, ComponentEntry, ComponentIndex};
+use index_cache::CachedFile;
+use progress::{IndexingProgress, Status, StatusNotification};
+use property_rename::PropertyTarget;
+use pull_diagnostics::{DocumentDiagnosticParams, DocumentDiagnosticReport};
+use type_hierarchy::{TypeHierarchyItem, TypeHierarchyItemParams, TypeHierarchyPrepareParams};
+use usages::{SelectorUsages, SelectorUsagesParams, UnusedComponent};
+
+/// The decorator of an exported class belongs to the export statement,
+/// hence a pattern for exported classes and one for the others.
 static FIND_COMPONENT_QUERY_STRING: &str = r#"
 (export_statement
   decorator: (decorator
@@ -19,17 +69,62 @@ static FIND_COMPONENT_QUERY_STRING: &str = r#"
       arguments: (arguments
         (object (pair
           key: (property_identifier) @prop-name
-          value: (string (string_fragment) @prop-value))))
+          value: (_) @prop-value)) @metadata)
     )
   )
   declaration: (class_declaration name: (type_identifier) @class-name) @declaration
-  (#eq? @dec-name Component)
+  (#match? @dec-name "^(Component|Directive)$")
+  (#eq? @prop-name selector)
+)
+
+(class_declaration
+  decorator: (decorator
+    (call_expression
+      function: (identifier) @dec-name
+      arguments: (arguments
+        (object (pair
+          key: (property_identifier) @prop-name
+          value: (_) @prop-value)) @metadata)
+    )
+  )
+  name: (type_identifier) @class-name
+  (#match? @dec-name "^(Component|Directive)$")
   (#eq? @prop-name selector)
+) @declaration"#;
+
+static PIPE_QUERY_STRING: &str = r#"
+(export_statement
+  decorator: (decorator
+    (call_expression
+      function: (identifier) @dec-name
+      arguments: (arguments
+        (object (pair
+          key: (property_identifier) @prop-name
+          value: (string (string_fragment) @pipe-name))))
+    )
+  )
+  declaration: (class_declaration name: (type_identifier) @class-name)
+  (#eq? @dec-name Pipe)
+  (#eq? @prop-name name)
+)"#;
+
+static MODULE_QUERY_STRING: &str = r#"
+(export_statement
+  decorator: (decorator
+    (call_expression
+      function: (identifier) @dec-name
+      arguments: (arguments (object) @metadata)
+    )
+  )
+  declaration: (class_declaration name: (type_identifier) @class-name)
+  (#eq? @dec-name NgModule)
 )"#;
 
 static INOUT_QUERY_STRING: &str = r#"
 (
-  (decorator (call_expression function: (identifier) @dec-name))
+  (decorator (call_expression
+    function: (identifier) @dec-name
+    arguments: (arguments . [(string (string_fragment) @alias) (object) @options]?)))
   .
   [
     (public_field_definition name: (property_identifier) @prop-name)
@@ -38,121 +133,874 @@ static INOUT_QUERY_STRING: &str = r#"
   (#match? @dec-name "Input|Output")
 )"#;
 
-#[derive(Debug)]
+static SIGNAL_QUERY_STRING: &str = r#"
+(public_field_definition
+  name: (property_identifier) @prop-name
+  value: (call_expression function: (_) @fn-name)
+  (#match? @fn-name "^(input|output|model)(\\.required)?$")
+)"#;
+
+/// Command clearing the index and indexing every workspace folder again.
+const REINDEX_COMMAND: &str = "angular.reindexWorkspace";
+
+/// Command locating the component of which a document is the `templateUrl`.
+const GOTO_OWNING_COMPONENT_COMMAND: &str = "angular.gotoOwningComponent";
+const FIND_UNUSED_COMPONENTS_COMMAND: &str = "angular.findUnusedComponents";
+
+/// Source of the document generations.
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Maximum number of files indexed at the same time, unless configured otherwise.
+const MAX_INDEXING_WORKERS: usize = 8;
+
+/// Maximum number of results of a workspace symbol search.
+const MAX_WORKSPACE_SYMBOLS: usize = 100;
+
+/// Pause in the edits of a document after which it gets analyzed.
+const ANALYSIS_DELAY: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ComponentKind {
+    Component,
+    Directive,
+}
+
+/// An `@Input()` or `@Output()` member of a component.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Property {
+    /// Name of the class member.
+    name: String,
+    /// Name given in the decorator, which templates must bind to instead.
+    alias: Option<String>,
+    /// Range of the alias in the component file, none for an alias the member only implies.
+    alias_range: Option<Range>,
+    /// Range of the member name in the component file.
+    range: Range,
+    /// Declared type of the bound value, or of the event payload for an output.
+    value_type: Option<String>,
+    /// Text of the JSDoc comment of the member.
+    documentation: Option<String>,
+    /// Whether templates must bind this input.
+    required: bool,
+    /// Values of an input typed as a union of string literals, like `'primary' | 'secondary'`.
+    allowed_values: Vec<String>,
+    /// Source text of the initial value of an input, like `'md'` for `size = 'md'` or `input('md')`.
+    default_value: Option<String>,
+}
+
+impl Property {
+    /// Name under which templates bind to this property.
+    fn binding_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Component {
+    kind: ComponentKind,
     selector: String,
+    selector_range: Range,
+    class_name: String,
+    file_url: Url,
+    class_name_range: Range,
+    /// Range of the whole class declaration.
+    declaration_range: Range,
+    template_range: Option<Range>,
+    /// External template given by `templateUrl`, resolved against `file_url`.
+    template_url: Option<Url>,
+    /// Style files given by `styleUrls` or `styleUrl`, resolved against `file_url`.
+    style_urls: Vec<Url>,
+    standalone: bool,
+    /// Classes made available to the template of a standalone component.
+    imports: Option<ClassList>,
+    /// Position right after the opening brace of the decorator metadata.
+    metadata_start: Position,
+    /// Text of the `/** */` comment before the class.
+    documentation: Option<String>,
+    /// Keys of the `host` metadata, like `[class.active]` or `(click)`, bound on the element itself.
+    host_bindings: Vec<String>,
+    /// Names given by `exportAs` to template reference variables, like `#tooltip="appTooltip"`.
+    export_as: Option<String>,
+    /// Names of the methods of the class, arrow function fields included.
+    methods: Vec<String>,
+    /// Name of the class this one extends, like `BaseComponent`.
+    super_class: Option<String>,
+    inputs: Vec<Property>,
+    outputs: Vec<Property>
+}
+
+/// An array of class names in decorator metadata, like `imports: [...]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ClassList {
+    names: Vec<String>,
+    /// Where a new class goes: after the last element, or inside the empty brackets.
+    insert_position: Position,
+}
+
+/// An `@NgModule()` class, scoping the templates of the components it declares.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NgModule {
+    class_name: String,
+    file_url: Url,
+    metadata_start: Position,
+    declarations: Option<ClassList>,
+    imports: Option<ClassList>,
+    exports: Option<ClassList>,
+}
+
+/// A `@Pipe()` class, used in template expressions by its name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Pipe {
+    name: String,
+    class_name: String,
     file_url: Url,
     class_name_range: Range,
-    inputs: Vec<String>,
-    outputs: Vec<String>
 }
 
-fn to_position(point: tree_sitter::Point) -> Position {
-    return Position {
+/// Text parsed by tree-sitter, whose byte columns LSP positions count in UTF-16 code units instead.
+trait SourceText {
+    /// Number of UTF-16 code units of the `bytes` range of the text.
+    fn utf16_len(&self, bytes: std::ops::Range<usize>) -> usize;
+}
+
+impl SourceText for Rope {
+    fn utf16_len(&self, bytes: std::ops::Range<usize>) -> usize {
+        self.get_byte_slice(bytes).map_or(0, |slice| slice.len_utf16_cu())
+    }
+}
+
+impl SourceText for str {
+    fn utf16_len(&self, bytes: std::ops::Range<usize>) -> usize {
+        self.get(bytes).map_or(0, |text| text.encode_utf16().count())
+    }
+}
+
+impl SourceText for [u8] {
+    fn utf16_len(&self, bytes: std::ops::Range<usize>) -> usize {
+        self.get(bytes).and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .map_or(0, |text| text.encode_utf16().count())
+    }
+}
+
+/// LSP position of the `byte` of `text`, at the tree-sitter `point`.
+fn to_position(text: &(impl SourceText + ?Sized), byte: usize, point: tree_sitter::Point) -> Position {
+    Position {
         line: point.row as u32,
-        character: point.column as u32
+        character: text.utf16_len(byte - point.column..byte) as u32,
     }
 }
 
-fn to_range(range: tree_sitter::Range) -> Range {
-    return Range {
-        start: to_position(range.start_point),
-        end: to_position(range.end_point)
+fn to_range(text: &(impl SourceText + ?Sized), range: tree_sitter::Range) -> Range {
+    Range {
+        start: to_position(text, range.start_byte, range.start_point),
+        end: to_position(text, range.end_byte, range.end_point),
     }
 }
 
-struct ComponentAnalyzer {
-    parser: Parser,
+/// Byte offset of an LSP position in `rope`, its character counting UTF-16 code units.
+fn to_offset(rope: &Rope, position: Position) -> Option<usize> {
+    let line_start = rope.try_line_to_char(position.line as usize).ok()?;
+    // A character past the end of the line stays on it, before its line break
+    let line_end = rope.get_line(position.line as usize).map_or(line_start, |line| {
+        let line_break = line.chars_at(line.len_chars()).reversed()
+            .take_while(|char| matches!(char, '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'))
+            .count();
+        line_start + line.len_chars() - line_break
+    });
+    let utf16_offset = (rope.char_to_utf16_cu(line_start) + position.character as usize).min(rope.char_to_utf16_cu(line_end));
+    let offset = rope.try_utf16_cu_to_char(utf16_offset).ok()?;
+    rope.try_char_to_byte(offset).ok()
+}
+
+fn byte_to_point(rope: &Rope, byte: usize) -> tree_sitter::Point {
+    let line = rope.byte_to_line(byte);
+    tree_sitter::Point { row: line, column: byte - rope.line_to_byte(line) }
+}
+
+/// Applies an incremental content change to `rope`, returning the edit to report to its tree
+/// and whether it may mask or unmask block syntax away from it: when it contains an `@`
+/// or a brace, either inserted or removed, or edits a line with an `@`, like a block name.
+fn apply_change(rope: &mut Rope, range: Range, text: &str) -> (tree_sitter::InputEdit, bool) {
+    let start_byte = to_offset(rope, range.start).unwrap_or(rope.len_bytes()).min(rope.len_bytes());
+    let old_end_byte = to_offset(rope, range.end).unwrap_or(rope.len_bytes()).clamp(start_byte, rope.len_bytes());
+    let start = rope.byte_to_char(start_byte);
+    let old_end = rope.byte_to_char(old_end_byte);
+    let start_position = byte_to_point(rope, start_byte);
+    let old_end_position = byte_to_point(rope, old_end_byte);
+    let is_block_syntax = |char: char| matches!(char, '@' | '{' | '}');
+    let edits_blocks = text.contains(is_block_syntax)
+        || rope.slice(start..old_end).chars().any(is_block_syntax)
+        || (start_position.row..=old_end_position.row).any(|line| rope.line(line).chars().any(|char| char == '@'));
+
+    rope.remove(start..old_end);
+    rope.insert(start, text);
+
+    let new_end_byte = start_byte + text.len();
+    let edit = tree_sitter::InputEdit {
+        start_byte,
+        old_end_byte,
+        new_end_byte,
+        start_position,
+        old_end_position,
+        new_end_position: byte_to_point(rope, new_end_byte),
+    };
+    (edit, edits_blocks)
+}
+
+fn range_contains(range: &Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+/// Text covered by `node`, sliced out of the document it was parsed from.
+fn node_text(node: &Node, rope: &Rope) -> Option<String> {
+    rope.get_byte_slice(node.start_byte()..node.end_byte()).map(String::from)
+}
+
+/// Finds the value of the `name` property in a decorator metadata object.
+fn find_property<'a>(object: &Node<'a>, name: &str, source: &[u8]) -> Option<Node<'a>> {
+    let mut cursor = object.walk();
+    let pair = object.named_children(&mut cursor)
+        .filter(|pair| pair.kind() == "pair")
+        .find(|pair| pair.child_by_field_name("key")
+            .and_then(|key| key.utf8_text(source).ok()) == Some(name));
+    pair.and_then(|pair| pair.child_by_field_name("value"))
+}
+
+/// Range of the text of a selector: a string, a template literal without
+/// substitutions, or the name of a top level `const` of either in the same file.
+fn selector_range(value: &Node, contents: &str) -> Option<tree_sitter::Range> {
+    match value.kind() {
+        "string" => string_content_range(value),
+        "template_string" => {
+            let mut cursor = value.walk();
+            let substituted = value.named_children(&mut cursor).any(|child| child.kind() == "template_substitution");
+            if substituted { None } else { string_content_range(value) }
+        }
+        "identifier" => {
+            let name = value.utf8_text(contents.as_bytes()).ok()?;
+            let mut root = *value;
+            while let Some(parent) = root.parent() {
+                root = parent;
+            }
+            let constant = top_level_const(&root, name, contents)?;
+            // Not another identifier, which could go round in circles
+            (constant.kind() != "identifier").then(|| selector_range(&constant, contents)).flatten()
+        }
+        _ => None,
+    }
+}
+
+/// Value of the top level `const name = value` declaration, exported or not.
+fn top_level_const<'a>(root: &Node<'a>, name: &str, contents: &str) -> Option<Node<'a>> {
+    let mut cursor = root.walk();
+    let statements: Vec<Node> = root.named_children(&mut cursor)
+        .map(|statement| match statement.kind() {
+            "export_statement" => statement.child_by_field_name("declaration").unwrap_or(statement),
+            _ => statement,
+        })
+        .filter(|statement| statement.kind() == "lexical_declaration"
+            && statement.child(0).is_some_and(|keyword| keyword.kind() == "const"))
+        .collect();
+    statements.iter()
+        .flat_map(|declaration| {
+            let mut cursor = declaration.walk();
+            declaration.named_children(&mut cursor).collect::<Vec<Node>>()
+        })
+        .filter(|declarator| declarator.kind() == "variable_declarator")
+        .find(|declarator| declarator.child_by_field_name("name")
+            .and_then(|declared| declared.utf8_text(contents.as_bytes()).ok()) == Some(name))
+        .and_then(|declarator| declarator.child_by_field_name("value"))
+}
+
+/// Range of the text of the `name` string property of a metadata object, quotes excluded.
+fn string_property_range(object: &Node, name: &str, contents: &str) -> Option<tree_sitter::Range> {
+    find_property(object, name, contents.as_bytes()).and_then(|value| string_content_range(&value))
+}
+
+fn is_true(object: &Node, name: &str, contents: &str) -> bool {
+    find_property(object, name, contents.as_bytes()).is_some_and(|value| value.kind() == "true")
+}
+
+/// Position right after the opening brace or bracket of `node`.
+fn inner_start(node: &Node, source: &[u8]) -> Position {
+    let start = to_position(source, node.start_byte(), node.start_position());
+    Position { character: start.character + 1, ..start }
+}
+
+/// Text of the class a class declaration extends, without its type arguments.
+fn super_class(declaration: &Node, contents: &str) -> Option<String> {
+    let mut cursor = declaration.walk();
+    let heritage = declaration.named_children(&mut cursor).find(|child| child.kind() == "class_heritage")?;
+    let mut cursor = heritage.walk();
+    let extends = heritage.named_children(&mut cursor).find(|child| child.kind() == "extends_clause")?;
+    extends.child_by_field_name("value")
+        .and_then(|value| value.utf8_text(contents.as_bytes()).ok())
+        .map(String::from)
+}
+
+/// Names of the methods of a class declaration, and of its fields set to a function.
+fn class_methods(declaration: &Node, contents: &str) -> Vec<String> {
+    let Some(body) = declaration.child_by_field_name("body") else {
+        return Vec::new();
+    };
+    let mut cursor = body.walk();
+    let members: Vec<Node> = body.named_children(&mut cursor).collect();
+    members.iter()
+        .filter(|member| match member.kind() {
+            "method_definition" => true,
+            "public_field_definition" => member.child_by_field_name("value")
+                .is_some_and(|value| matches!(value.kind(), "arrow_function" | "function" | "function_expression")),
+            _ => false,
+        })
+        .filter_map(|member| member.child_by_field_name("name"))
+        .filter_map(|name| name.utf8_text(contents.as_bytes()).ok())
+        .map(String::from)
+        .collect()
+}
+
+/// Keys of the `host` object property of a metadata object, quoted or not.
+fn host_bindings(object: &Node, contents: &str) -> Vec<String> {
+    let Some(host) = find_property(object, "host", contents.as_bytes()).filter(|value| value.kind() == "object") else {
+        return Vec::new();
+    };
+    let mut cursor = host.walk();
+    let keys: Vec<Node> = host.named_children(&mut cursor)
+        .filter(|pair| pair.kind() == "pair")
+        .filter_map(|pair| pair.child_by_field_name("key"))
+        .collect();
+    keys.iter()
+        .filter_map(|key| match string_content_range(key) {
+            Some(range) => contents.get(range.start_byte..range.end_byte),
+            None => key.utf8_text(contents.as_bytes()).ok(),
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// Class names of the `name` array property of a metadata object.
+fn find_class_list(object: &Node, name: &str, source: &[u8]) -> Option<ClassList> {
+    let array = find_property(object, name, source).filter(|value| value.kind() == "array")?;
+    let mut cursor = array.walk();
+    let elements: Vec<Node> = array.named_children(&mut cursor)
+        .filter(|element| element.kind() != "comment")
+        .collect();
+    let insert_position = elements.last()
+        .map(|element| to_position(source, element.end_byte(), element.end_position()))
+        .unwrap_or_else(|| inner_start(&array, source));
+    Some(ClassList {
+        names: elements.iter()
+            .filter(|element| element.kind() == "identifier")
+            .filter_map(|element| element.utf8_text(source).ok())
+            .map(String::from)
+            .collect(),
+        insert_position,
+    })
+}
+
+/// Texts of the strings of the `name` array property of a metadata object.
+fn string_list<'a>(object: &Node, name: &str, contents: &'a str) -> Vec<&'a str> {
+    let Some(array) = find_property(object, name, contents.as_bytes()).filter(|value| value.kind() == "array") else {
+        return Vec::new();
+    };
+    let mut cursor = array.walk();
+    let elements: Vec<Node> = array.named_children(&mut cursor).collect();
+    elements.iter()
+        .filter_map(string_content_range)
+        .filter_map(|range| contents.get(range.start_byte..range.end_byte))
+        .collect()
+}
+
+/// Text of the `/** */` comment before a class member past its decorators,
+/// or before a class or its export statement.
+fn doc_comment(member: &Node, source: &[u8]) -> Option<String> {
+    let mut sibling = member.prev_named_sibling();
+    while let Some(decorator) = sibling.filter(|sibling| sibling.kind() == "decorator") {
+        sibling = decorator.prev_named_sibling();
+    }
+    let comment = sibling.filter(|sibling| sibling.kind() == "comment")?.utf8_text(source).ok()?;
+    let text = comment.strip_prefix("/**")?.strip_suffix("*/")?;
+    let lines: Vec<&str> = text.lines()
+        .map(|line| line.trim().trim_start_matches('*').trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    Some(lines.join("\n"))
+}
+
+/// First type argument of `node`, like the `T` of `EventEmitter<T>` or `output<T>()`.
+fn type_argument_node<'a>(node: &Node<'a>) -> Option<Node<'a>> {
+    node.child_by_field_name("type_arguments")?.named_child(0)
+}
+
+fn type_argument(node: &Node, source: &[u8]) -> Option<String> {
+    type_argument_node(node)?.utf8_text(source).ok().map(String::from)
+}
+
+/// Strings of a union of string literals type, which may also allow `undefined` or `null`.
+fn string_literals(type_node: &Node, contents: &str) -> Option<Vec<String>> {
+    match type_node.kind() {
+        "union_type" | "parenthesized_type" => {
+            let mut cursor = type_node.walk();
+            let members: Vec<Node> = type_node.named_children(&mut cursor).collect();
+            let mut values = Vec::new();
+            for member in members {
+                values.append(&mut string_literals(&member, contents)?);
+            }
+            Some(values)
+        }
+        "literal_type" => {
+            let literal = type_node.named_child(0)?;
+            match literal.kind() {
+                "undefined" | "null" => Some(Vec::new()),
+                _ => {
+                    let range = string_content_range(&literal)?;
+                    Some(vec![String::from(contents.get(range.start_byte..range.end_byte)?)])
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+fn allowed_values(type_node: Option<Node>, contents: &str) -> Vec<String> {
+    type_node.and_then(|type_node| string_literals(&type_node, contents)).unwrap_or_default()
+}
+
+/// Range of the text between the quotes of a string or template string.
+fn string_content_range(node: &Node) -> Option<tree_sitter::Range> {
+    if node.kind() != "string" && node.kind() != "template_string" {
+        return None;
+    }
+    let range = node.range();
+    Some(tree_sitter::Range {
+        start_byte: range.start_byte + 1,
+        end_byte: range.end_byte - 1,
+        start_point: tree_sitter::Point { row: range.start_point.row, column: range.start_point.column + 1 },
+        end_point: tree_sitter::Point { row: range.end_point.row, column: range.end_point.column - 1 },
+    })
+}
+
+/// One component per selector of a `'app-card, [appCard]'` selector list,
+/// each with the range of its own selector.
+fn split_selectors(component: Component) -> Vec<Component> {
+    let mut start = 0;
+    let mut components = Vec::new();
+    for part in component.selector.split(',') {
+        let selector = part.trim();
+        let selector_start = start + part.len() - part.trim_start().len();
+        start += part.len() + 1;
+        if selector.is_empty() {
+            continue;
+        }
+        let range_start = Position {
+            character: component.selector_range.start.character + component.selector[..selector_start].encode_utf16().count() as u32,
+            ..component.selector_range.start
+        };
+        components.push(Component {
+            selector: String::from(selector),
+            selector_range: Range::new(range_start, Position {
+                character: range_start.character + selector.encode_utf16().count() as u32,
+                ..range_start
+            }),
+            ..component.clone()
+        });
+    }
+    components
+}
+
+/// Compiled queries of the TypeScript analysis along with their capture indices,
+/// shared by every analyzer as compiling them takes a while.
+struct ComponentQueries {
     component_query: Query,
+    dec_name_idx: u32,
     selector_idx: u32,
     class_name_idx: u32,
     class_dec_idx: u32,
+    metadata_idx: u32,
     inout_query: Query,
     type_idx: u32,
     prop_idx: u32,
+    alias_idx: u32,
+    options_idx: u32,
+    signal_query: Query,
+    signal_fn_idx: u32,
+    signal_prop_idx: u32,
+    pipe_query: Query,
+    pipe_name_idx: u32,
+    pipe_class_idx: u32,
+    module_query: Query,
+    module_metadata_idx: u32,
+    module_class_idx: u32,
 }
 
-impl ComponentAnalyzer {
-    fn new() -> Option<Self> {
-        let ts_lang = tree_sitter_typescript::language_typescript();
-        let mut parser = Parser::new();
-        parser.set_language(ts_lang).ok()?;
+impl ComponentQueries {
+    fn new(ts_lang: Language) -> Option<Self> {
         let component_query = Query::new(ts_lang, FIND_COMPONENT_QUERY_STRING).ok()?;
         let inout_query = Query::new(ts_lang, INOUT_QUERY_STRING).ok()?;
-        return Some(ComponentAnalyzer {
-            parser: parser,
+        let signal_query = Query::new(ts_lang, SIGNAL_QUERY_STRING).ok()?;
+        let pipe_query = Query::new(ts_lang, PIPE_QUERY_STRING).ok()?;
+        let module_query = Query::new(ts_lang, MODULE_QUERY_STRING).ok()?;
+        Some(ComponentQueries {
+            dec_name_idx: component_query.capture_index_for_name("dec-name")?,
             selector_idx: component_query.capture_index_for_name("prop-value")?,
             class_name_idx: component_query.capture_index_for_name("class-name")?,
             class_dec_idx: component_query.capture_index_for_name("declaration")?,
-            component_query: component_query,
+            metadata_idx: component_query.capture_index_for_name("metadata")?,
+            component_query,
             type_idx: inout_query.capture_index_for_name("dec-name")?,
             prop_idx: inout_query.capture_index_for_name("prop-name")?,
-            inout_query: inout_query
+            alias_idx: inout_query.capture_index_for_name("alias")?,
+            options_idx: inout_query.capture_index_for_name("options")?,
+            inout_query,
+            signal_fn_idx: signal_query.capture_index_for_name("fn-name")?,
+            signal_prop_idx: signal_query.capture_index_for_name("prop-name")?,
+            signal_query,
+            pipe_name_idx: pipe_query.capture_index_for_name("pipe-name")?,
+            pipe_class_idx: pipe_query.capture_index_for_name("class-name")?,
+            pipe_query,
+            module_metadata_idx: module_query.capture_index_for_name("metadata")?,
+            module_class_idx: module_query.capture_index_for_name("class-name")?,
+            module_query
         })
     }
+}
 
-    async fn analyze_file(&mut self, file_path: &Path, component_index: &Arc<DashMap<String, Component>>) {
-        debug!("FILE {:?}", file_path);
-    
-        let contents = fs::read_to_string(file_path).expect("Should have been able to read the file");
-        let tree = self.parser.parse(&contents, None).unwrap();
-    
+static COMPONENT_QUERIES: LazyLock<Option<ComponentQueries>> =
+    LazyLock::new(|| ComponentQueries::new(tree_sitter_typescript::language_typescript()));
+static TSX_COMPONENT_QUERIES: LazyLock<Option<ComponentQueries>> =
+    LazyLock::new(|| ComponentQueries::new(tree_sitter_typescript::language_tsx()));
+
+/// Analysis of TypeScript sources, each analyzer having its own parser.
+struct ComponentAnalyzer {
+    parser: Parser,
+    /// Queries of the grammar the parser is set to.
+    queries: &'static ComponentQueries,
+}
+
+impl ComponentAnalyzer {
+    fn new() -> Option<Self> {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_typescript::language_typescript()).ok()?;
+        Some(ComponentAnalyzer {
+            parser,
+            queries: COMPONENT_QUERIES.as_ref()?,
+        })
+    }
+
+    /// Switches to the grammar of `file_url`, TSX for `.tsx` files and TypeScript otherwise,
+    /// the queries of one grammar not running on the trees of the other.
+    fn use_grammar_of(&mut self, file_url: &Url) -> Option<()> {
+        let (language, queries) = if file_url.path().ends_with(".tsx") {
+            (tree_sitter_typescript::language_tsx(), &TSX_COMPONENT_QUERIES)
+        } else {
+            (tree_sitter_typescript::language_typescript(), &COMPONENT_QUERIES)
+        };
+        if self.parser.language() != Some(language) {
+            self.parser.set_language(language).ok()?;
+            self.queries = queries.as_ref()?;
+        }
+        Some(())
+    }
+
+    fn parse(&mut self, contents: &str, file_url: &Url) -> Option<Tree> {
+        self.use_grammar_of(file_url)?;
+        self.parser.parse(contents, None)
+    }
+
+    fn analyze_source(&mut self, contents: &str, file_url: &Url) -> Vec<Component> {
+        match self.parse(contents, file_url) {
+            Some(tree) => self.analyze_components(&tree, contents, file_url),
+            None => {
+                warn!("Error parsing file {}", file_url);
+                Vec::new()
+            }
+        }
+    }
+
+    fn analyze_components(&self, tree: &Tree, contents: &str, file_url: &Url) -> Vec<Component> {
         let mut component_query_cursor = QueryCursor::new();
-        let component_matches = component_query_cursor.matches(&self.component_query, tree.root_node(), contents.as_bytes());
-    
-        for component_match in component_matches {
-            let classname_node = component_match.nodes_for_capture_index(self.class_name_idx).next().unwrap();
-            let declaration = component_match.nodes_for_capture_index(self.class_dec_idx).next().unwrap();
-    
-            let selector = component_match.nodes_for_capture_index(self.selector_idx).next()
-                .and_then(|x| x.utf8_text(&contents.as_bytes()).ok()).unwrap();
-            let class_name = classname_node.utf8_text(&contents.as_bytes()).ok().unwrap();
-            debug!("COMP {:?} -> {:?}",selector, class_name);
-    
-            let mut inputs: Vec<String> = Vec::new();
-            let mut outputs: Vec<String> = Vec::new();
-            let mut inout_query_cursor = QueryCursor::new();
-            let inout_matches = inout_query_cursor.matches(&self.inout_query, declaration, contents.as_bytes());
-            for inout_match in inout_matches {
-                let prop_type = inout_match.nodes_for_capture_index(self.type_idx).next()
-                    .and_then(|node| node.utf8_text(&contents.as_bytes()).ok()).unwrap();
-                let prop_name = inout_match.nodes_for_capture_index(self.prop_idx).next()
-                    .and_then(|node| node.utf8_text(&contents.as_bytes()).ok()).unwrap();
-                if prop_type.eq("Input") {
-                    inputs.push(String::from(prop_name));
-                } else {
-                    outputs.push(String::from(prop_name));
+        let component_matches = component_query_cursor.matches(&self.queries.component_query, tree.root_node(), contents.as_bytes());
+        component_matches
+            .filter_map(|component_match| {
+                let component = self.analyze_component(&component_match, contents, file_url);
+                if component.is_none() {
+                    warn!("Skipping malformed component in {}", file_url);
                 }
-                debug!("  PROP {:?} {:?}", prop_type, prop_name);
-            }
-            let base_url = Url::parse("file://");
-            let options = Url::options().base_url(base_url.as_ref().ok());
-            let component = Component {
-                selector: String::from(selector),
-                file_url: file_path.to_str().and_then(|s| options.parse(s).ok()).unwrap(),
-                class_name_range: to_range(classname_node.range()),
-                inputs: inputs,
-                outputs: outputs
+                component
+            })
+            .flat_map(split_selectors)
+            .collect()
+    }
+
+    fn analyze_pipes(&self, tree: &Tree, contents: &str, file_url: &Url) -> Vec<Pipe> {
+        let mut pipe_query_cursor = QueryCursor::new();
+        let pipe_matches = pipe_query_cursor.matches(&self.queries.pipe_query, tree.root_node(), contents.as_bytes());
+        pipe_matches
+            .filter_map(|pipe_match| {
+                let name = pipe_match.nodes_for_capture_index(self.queries.pipe_name_idx).next()?;
+                let class_name = pipe_match.nodes_for_capture_index(self.queries.pipe_class_idx).next()?;
+                debug!("PIPE {:?}", name.utf8_text(contents.as_bytes()));
+                Some(Pipe {
+                    name: String::from(name.utf8_text(contents.as_bytes()).ok()?),
+                    class_name: String::from(class_name.utf8_text(contents.as_bytes()).ok()?),
+                    file_url: file_url.clone(),
+                    class_name_range: to_range(contents, class_name.range()),
+                })
+            })
+            .collect()
+    }
+
+    fn analyze_modules(&self, tree: &Tree, contents: &str, file_url: &Url) -> Vec<NgModule> {
+        let mut module_query_cursor = QueryCursor::new();
+        let module_matches = module_query_cursor.matches(&self.queries.module_query, tree.root_node(), contents.as_bytes());
+        module_matches
+            .filter_map(|module_match| {
+                let metadata = module_match.nodes_for_capture_index(self.queries.module_metadata_idx).next()?;
+                let class_name = module_match.nodes_for_capture_index(self.queries.module_class_idx).next()?;
+                debug!("MODULE {:?}", class_name.utf8_text(contents.as_bytes()));
+                Some(NgModule {
+                    class_name: String::from(class_name.utf8_text(contents.as_bytes()).ok()?),
+                    file_url: file_url.clone(),
+                    metadata_start: inner_start(&metadata, contents.as_bytes()),
+                    declarations: find_class_list(&metadata, "declarations", contents.as_bytes()),
+                    imports: find_class_list(&metadata, "imports", contents.as_bytes()),
+                    exports: find_class_list(&metadata, "exports", contents.as_bytes()),
+                })
+            })
+            .collect()
+    }
+
+    fn analyze_component(&self, component_match: &QueryMatch, contents: &str, file_url: &Url) -> Option<Component> {
+        let capture = |index| component_match.nodes_for_capture_index(index).next();
+        let dec_name = capture(self.queries.dec_name_idx)?;
+        let classname_node = capture(self.queries.class_name_idx)?;
+        let declaration = capture(self.queries.class_dec_idx)?;
+        let metadata = capture(self.queries.metadata_idx)?;
+        let selector_node = capture(self.queries.selector_idx)?;
+
+        let class_name = classname_node.utf8_text(contents.as_bytes()).ok()?;
+        let Some(selector_range) = selector_range(&selector_node, contents) else {
+            warn!("Skipping {} of {}, its selector isn't a constant string", class_name, file_url);
+            return None;
+        };
+        let selector = contents.get(selector_range.start_byte..selector_range.end_byte)?;
+        let kind = match dec_name.utf8_text(contents.as_bytes()) {
+            Ok("Directive") => ComponentKind::Directive,
+            _ => ComponentKind::Component,
+        };
+        debug!("{:?} {:?} -> {:?}", kind, selector, class_name);
+
+        let template_range = find_property(&metadata, "template", contents.as_bytes())
+            .and_then(|template| string_content_range(&template))
+            .map(|range| to_range(contents, range));
+        let template_url = find_property(&metadata, "templateUrl", contents.as_bytes())
+            .and_then(|template_url| string_content_range(&template_url))
+            .and_then(|range| contents.get(range.start_byte..range.end_byte))
+            .and_then(|path| file_url.join(path).ok());
+        let style_urls = string_list(&metadata, "styleUrls", contents).into_iter()
+            .chain(string_property_range(&metadata, "styleUrl", contents)
+                .and_then(|range| contents.get(range.start_byte..range.end_byte)))
+            .filter_map(|path| file_url.join(path).ok())
+            .collect();
+        let imports = find_class_list(&metadata, "imports", contents.as_bytes());
+        let standalone = match find_property(&metadata, "standalone", contents.as_bytes()) {
+            Some(standalone) => standalone.kind() == "true",
+            None => imports.is_some(),
+        };
+        let (inputs, outputs) = self.analyze_properties(&declaration, contents);
+        let documented = declaration.parent()
+            .filter(|export| export.kind() == "export_statement")
+            .unwrap_or(declaration);
+        let documentation = doc_comment(&documented, contents.as_bytes());
+
+        Some(Component {
+            kind,
+            selector: String::from(selector),
+            selector_range: to_range(contents, selector_range),
+            class_name: String::from(class_name),
+            file_url: file_url.clone(),
+            class_name_range: to_range(contents, classname_node.range()),
+            declaration_range: to_range(contents, declaration.range()),
+            template_range,
+            template_url,
+            style_urls,
+            standalone,
+            imports,
+            metadata_start: inner_start(&metadata, contents.as_bytes()),
+            documentation,
+            host_bindings: host_bindings(&metadata, contents),
+            export_as: string_property_range(&metadata, "exportAs", contents)
+                .and_then(|range| contents.get(range.start_byte..range.end_byte))
+                .map(String::from),
+            methods: class_methods(&declaration, contents),
+            super_class: super_class(&declaration, contents),
+            inputs,
+            outputs
+        })
+    }
+
+    /// Inputs and outputs declared in a component class, through decorators or signals.
+    fn analyze_properties(&self, declaration: &Node, contents: &str) -> (Vec<Property>, Vec<Property>) {
+        let text = |node: Option<Node>| node.and_then(|node| node.utf8_text(contents.as_bytes()).ok());
+        let mut inputs: Vec<Property> = Vec::new();
+        let mut outputs: Vec<Property> = Vec::new();
+
+        let mut inout_query_cursor = QueryCursor::new();
+        let inout_matches = inout_query_cursor.matches(&self.queries.inout_query, *declaration, contents.as_bytes());
+        for inout_match in inout_matches {
+            let prop_node = inout_match.nodes_for_capture_index(self.queries.prop_idx).next();
+            let (Some(prop_type), Some(prop_name), Some(prop_node)) = (
+                text(inout_match.nodes_for_capture_index(self.queries.type_idx).next()), text(prop_node), prop_node) else {
+                continue;
+            };
+            // Either `@Input('alias')` or `@Input({ alias: 'alias', required: true })`
+            let options = inout_match.nodes_for_capture_index(self.queries.options_idx).next();
+            let alias_range = inout_match.nodes_for_capture_index(self.queries.alias_idx).next().map(|alias| alias.range())
+                .or_else(|| options.and_then(|options| string_property_range(&options, "alias", contents)));
+            let alias = alias_range.and_then(|range| contents.get(range.start_byte..range.end_byte)).map(String::from);
+            let required = options.is_some_and(|options| is_true(&options, "required", contents));
+            debug!("  PROP {:?} {:?} {:?}", prop_type, prop_name, alias);
+            let field = prop_node.parent();
+            let annotation = field.and_then(|field| field.child_by_field_name("type"))
+                .and_then(|annotation| annotation.named_child(0));
+            let value_type = if prop_type.eq("Input") {
+                annotation.and_then(|annotation| text(Some(annotation))).map(String::from)
+            } else {
+                // Either `x: EventEmitter<T>` or `x = new EventEmitter<T>()`
+                annotation.and_then(|generic| type_argument(&generic, contents.as_bytes()))
+                    .or_else(|| field.and_then(|field| field.child_by_field_name("value"))
+                        .and_then(|value| type_argument(&value, contents.as_bytes())))
+            };
+            let property = Property {
+                name: String::from(prop_name),
+                alias,
+                alias_range: alias_range.map(|range| to_range(contents, range)),
+                range: to_range(contents, prop_node.range()),
+                value_type,
+                documentation: field.and_then(|field| doc_comment(&field, contents.as_bytes())),
+                required: required && prop_type.eq("Input"),
+                allowed_values: allowed_values(annotation.filter(|_| prop_type.eq("Input")), contents),
+                default_value: field.and_then(|field| field.child_by_field_name("value"))
+                    .filter(|_| prop_type.eq("Input"))
+                    .and_then(|value| text(Some(value)))
+                    .map(String::from),
+            };
+            if prop_type.eq("Input") {
+                inputs.push(property);
+            } else {
+                outputs.push(property);
+            }
+        }
+
+        let mut signal_query_cursor = QueryCursor::new();
+        let signal_matches = signal_query_cursor.matches(&self.queries.signal_query, *declaration, contents.as_bytes());
+        for signal_match in signal_matches {
+            let prop_node = signal_match.nodes_for_capture_index(self.queries.signal_prop_idx).next();
+            let (Some(function), Some(prop_name), Some(prop_node)) = (
+                text(signal_match.nodes_for_capture_index(self.queries.signal_fn_idx).next()), text(prop_node), prop_node) else {
+                continue;
+            };
+            debug!("  SIGNAL {:?} {:?}", function, prop_name);
+            let call = prop_node.parent().and_then(|field| field.child_by_field_name("value"));
+            // The options, like `{ alias: 'alias' }`, come last in the arguments of the call
+            let options = call.and_then(|call| call.child_by_field_name("arguments"))
+                .and_then(|arguments| arguments.named_child(arguments.named_child_count().checked_sub(1)?))
+                .filter(|options| options.kind() == "object");
+            let alias_range = options.and_then(|options| string_property_range(&options, "alias", contents));
+            // Required inputs take their options first, the others their initial value
+            let default_value = call.and_then(|call| call.child_by_field_name("arguments"))
+                .and_then(|arguments| arguments.named_child(0))
+                .filter(|_| matches!(function, "input" | "model"))
+                .and_then(|value| text(Some(value)))
+                .map(String::from);
+            let property = Property {
+                name: String::from(prop_name),
+                alias: alias_range.and_then(|range| contents.get(range.start_byte..range.end_byte)).map(String::from),
+                alias_range: alias_range.map(|range| to_range(contents, range)),
+                range: to_range(contents, prop_node.range()),
+                value_type: call.and_then(|call| type_argument(&call, contents.as_bytes())),
+                documentation: prop_node.parent().and_then(|field| doc_comment(&field, contents.as_bytes())),
+                required: function.ends_with(".required"),
+                allowed_values: allowed_values(call.and_then(|call| type_argument_node(&call)), contents),
+                default_value,
             };
-            component_index.insert(component.selector.clone(), component);
+            match function.split('.').next() {
+                Some("input") => inputs.push(property),
+                Some("output") => outputs.push(property),
+                _ => {
+                    // A model is an input plus the matching `<name>Change` output
+                    outputs.push(Property {
+                        alias: Some(format!("{}Change", property.binding_name())),
+                        alias_range: None,
+                        required: false,
+                        allowed_values: Vec::new(),
+                        default_value: None,
+                        ..property.clone()
+                    });
+                    inputs.push(property);
+                }
+            }
         }
+        (inputs, outputs)
+    }
+
+    /// Indexes the file at `file_path`, from its content in the editor when it's open.
+    /// A file over `max_file_size` bytes on disk, likely generated or bundled, is left out.
+    fn analyze_file(&mut self, file_path: &Path, document_map: &DashMap<String, Rope>,
+            component_index: &ComponentIndex, max_file_size: u64) -> io::Result<()> {
+        debug!("FILE {:?}", file_path);
+
+        // Escapes what a URL would take apart, like `#`, and handles drive letters
+        let file_url = Url::from_file_path(file_path)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Path is not absolute"))?;
+        let contents = match document_map.get(file_url.as_str()) {
+            Some(rope) => rope.to_string(),
+            None => {
+                let size = fs::metadata(file_path)?.len();
+                if size > max_file_size {
+                    warn!("Skipping {:?}, its {} bytes are over the maximum of {}", file_path, size, max_file_size);
+                    component_index.remove_file(&file_url);
+                    return Ok(());
+                }
+                fs::read_to_string(file_path)?
+            }
+        };
+        self.analyze_contents(&contents, &file_url, component_index);
+        Ok(())
+    }
+
+    /// Replaces whatever the index knew of `file_url` with what `contents` declares.
+    fn analyze_contents(&mut self, contents: &str, file_url: &Url, component_index: &ComponentIndex) {
+        let Some(tree) = self.parse(contents, file_url) else {
+            warn!("Error parsing file {}", file_url);
+            component_index.remove_file(file_url);
+            return;
+        };
+        component_index.update_file(file_url,
+            self.analyze_components(&tree, contents, file_url),
+            self.analyze_pipes(&tree, contents, file_url),
+            self.analyze_modules(&tree, contents, file_url));
     }
 
-    async fn analyze_workspace(&mut self, workspace_root: &str, component_index: &Arc<DashMap<String, Component>>) {
+    /// Files of the workspace matching any of the component `globs`.
+    fn workspace_files(workspace_root: &str, globs: &[String]) -> Vec<PathBuf> {
         debug!("WORKSPACE {:?}", workspace_root);
-        match glob((String::from(workspace_root) + "/src/**/*.ts").as_str()) {
-            Ok(pattern) => {
-                for entry in pattern {
-                    match entry {
-                        Ok(path) => self.analyze_file(path.as_path(), &component_index).await,
-                        Err(e) => warn!("Error getting file {:?}", e),
+        let mut files = Vec::new();
+        for component_glob in globs {
+            match glob(&format!("{}/{}", workspace_root, component_glob)) {
+                Ok(pattern) => {
+                    for entry in pattern {
+                        match entry {
+                            Ok(path) => files.push(path),
+                            Err(e) => warn!("Error getting file {:?}", e),
+                        }
                     }
                 }
-            }
-            Err(e) => {
-                warn!("Error making glob pattern {:?}", e);
+                Err(e) => {
+                    warn!("Error making glob pattern {:?}", e);
+                }
             }
         }
+        files
     }
 }
 
@@ -165,91 +1013,438 @@ impl HtmlAnalyzer {
         let html_lang = tree_sitter_html::language();
         let mut parser = Parser::new();
         parser.set_language(html_lang).unwrap();
-        return HtmlAnalyzer {
-            parser: parser
-        };
+        HtmlAnalyzer {
+            parser
+        }
+    }
+
+    fn parse(&mut self, rope: &Rope, old_tree: Option<&Tree>) -> Tree {
+        let source = control_flow::mask_blocks(&rope.to_string());
+        self.parser.parse(&source, old_tree).unwrap()
+    }
+}
+
+/// An HTML template embedded in a TypeScript document via `template:`.
+struct InlineTemplate {
+    /// Byte offset of the template content in the host document.
+    start: usize,
+    /// Position of the template content in the host document.
+    start_position: Position,
+    /// Component declaring the template, as analyzed from the host document content.
+    owner: Component,
+    rope: Rope,
+    tree: Tree,
+}
+
+impl InlineTemplate {
+    fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.start + self.rope.len_bytes()
+    }
+
+    /// Translates a position in the template into host document coordinates: only
+    /// the first line of the template shares its line with the host code before it.
+    fn to_host_position(&self, position: Position) -> Position {
+        Position {
+            line: position.line + self.start_position.line,
+            character: if position.line == 0 {
+                position.character + self.start_position.character
+            } else {
+                position.character
+            }
+        }
+    }
+
+    /// Translates a range computed on the template back into host document coordinates.
+    fn to_host_range(&self, range: Range) -> Range {
+        Range::new(self.to_host_position(range.start), self.to_host_position(range.end))
     }
 }
 
+/// Deepest node of one of `types` containing `offset`, its ends included. At the boundary
+/// of two nodes, the one ending at `offset` comes first, but a token like the `<`
+/// before a tag name doesn't hide the node starting there.
 fn find_node<'a>(node: &Node<'a>, offset: usize, types: Vec<&str>) -> Option<Node<'a>> {
+    find_deepest(*node, offset, &types)
+}
+
+fn find_deepest<'a>(node: Node<'a>, offset: usize, types: &[&str]) -> Option<Node<'a>> {
+    if offset < node.start_byte() || offset > node.end_byte() {
+        return None;
+    }
     let mut cursor = node.walk();
-    loop {
-        if cursor.node().start_byte() <= offset && offset <= cursor.node().end_byte() {
-            if types.contains(&cursor.node().kind()) {
-                return Some(cursor.node());
-            }
-            if ! cursor.goto_first_child() {
-                return None;
+    let children: Vec<Node<'a>> = node.children(&mut cursor).collect();
+    children.into_iter()
+        .find_map(|child| find_deepest(child, offset, types))
+        .or_else(|| types.contains(&node.kind()).then_some(node))
+}
+
+/// Calls `f` on every node of the tree rooted at `node`, in document order.
+fn walk_tree<'a>(node: &Node<'a>, f: &mut dyn FnMut(Node<'a>)) {
+    let mut cursor = node.walk();
+    'outer: loop {
+        f(cursor.node());
+        if cursor.goto_first_child() {
+            continue;
+        }
+        loop {
+            if cursor.goto_next_sibling() {
+                continue 'outer;
             }
-        } else {
-            if ! cursor.goto_next_sibling() {
-                return None;
+            if ! cursor.goto_parent() {
+                break 'outer;
             }
         }
     }
 }
 
-fn completion(node: &Node, offset: usize, rope: &Rope, components: &Arc<DashMap<String, Component>>) -> Vec<CompletionItem> {
-    find_node(node, offset, vec![ "start_tag", "self_closing_tag" ])
-        .and_then(|start_tag| {
-            let tag_name = find_node(&start_tag, offset, vec![ "tag_name" ]);
-            if tag_name.is_some() {
-                Some(components.iter()
-                    .map(|component| CompletionItem {
-                        label: component.selector.clone(),
-                        kind: Some(CompletionItemKind::KEYWORD),
-                        ..Default::default()
-                    })
-                    .collect())
-            } else {
-                let attr_name = find_node(&start_tag, offset, vec![ "attribute_name" ]);
-                fn make_completions(elements: &Vec<String>, template: &str) -> Vec<CompletionItem> {
-                    elements.iter().map(|input| CompletionItem {
-                        label: input.clone(),
-                        kind: Some(CompletionItemKind::FIELD),
-                        insert_text: Some(template.replace("{}", input)),
-                        insert_text_format: Some(InsertTextFormat::SNIPPET),
-                        ..Default::default()
-                    }).collect()
-                }
-                attr_name.and_then(|_| start_tag.named_child(0))
-                    .and_then(|node| rope.slice(node.range().start_byte..node.range().end_byte).as_str())
-                    .and_then(|tag_name| components.get(tag_name))
-                    .and_then(|component| {
-                        let mut completions = make_completions(&component.inputs, "[{}]=\"$0\"");
-                        completions.append(&mut make_completions(&component.outputs, "({})=\"$0\""));
-                        Some(completions)
-                    })
-            }
-        })
-        .unwrap_or(Vec::new())
+/// Name of a tag without its namespace, like `svg:`, along with the range of that name.
+fn element_name_range(tag_name: &Node, rope: &Rope) -> Option<(String, Range)> {
+    let text = node_text(tag_name, rope)?;
+    let name = element_name(&text);
+    let range = to_range(rope, tag_name.range());
+    let namespace = (text.len() - name.len()) as u32;
+    Some((String::from(name), Range::new(Position { character: range.start.character + namespace, ..range.start }, range.end)))
 }
 
-struct Backend {
-    client: Client,
-    components: Arc<DashMap<String, Component>>,
-    document_map: DashMap<String, Rope>,
-    ast_map: DashMap<String, Tree>
+fn find_tag_references(node: &Node, rope: &Rope, selector: &str) -> Vec<Range> {
+    let mut ranges = Vec::new();
+    walk_tree(node, &mut |node| {
+        let Some((name, range)) = element_name_range(&node, rope).filter(|_| node.kind() == "tag_name") else {
+            return;
+        };
+        if name == selector {
+            ranges.push(range);
+        }
+    });
+    ranges
 }
 
-#[tower_lsp::async_trait]
-impl LanguageServer for Backend {
-    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
-        if let Some(workspaces) = params.workspace_folders {
-            let component_index = self.components.clone();
-            tokio::spawn(async move {
-                match ComponentAnalyzer::new() {
-                    Some(mut analyzer) => analyzer.analyze_workspace(workspaces[0].uri.path(), &component_index).await,
-                    None => error!("Error building analyzer")
+/// Checks that `name` can be used as an element selector.
+fn is_valid_selector(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Attribute name matched by a `[attribute]` directive selector.
+fn attribute_selector(selector: &str) -> Option<&str> {
+    selector.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingKind {
+    Property,
+    Event,
+    TwoWay,
+}
+
+/// Splits a `[input]`, `(output)` or `[(model)]` attribute name into its kind and bound name.
+fn parse_binding(attribute: &str) -> Option<(BindingKind, &str)> {
+    if let Some(name) = attribute.strip_prefix("[(").and_then(|name| name.strip_suffix(")]")) {
+        Some((BindingKind::TwoWay, name))
+    } else if let Some(name) = attribute.strip_prefix('[').and_then(|name| name.strip_suffix(']')) {
+        Some((BindingKind::Property, name))
+    } else {
+        attribute.strip_prefix('(').and_then(|name| name.strip_suffix(')'))
+            .map(|name| (BindingKind::Event, name))
+    }
+}
+
+/// The component of the tag, if any, followed by the attribute directives that
+/// the attributes of `start_tag` already apply, like the `appTooltip` of `<div appTooltip>`.
+fn bindable_components(start_tag: &Node, rope: &Rope, components: &ComponentIndex) -> Vec<Component> {
+    let mut bindable: Vec<Component> = start_tag.named_child(0)
+        .and_then(|node| node_text(&node, rope))
+        .and_then(|tag_name| components.get(&tag_name))
+        .map(|component| component.clone())
+        .into_iter()
+        .collect();
+    let mut cursor = start_tag.walk();
+    let attribute_names: Vec<String> = start_tag.named_children(&mut cursor)
+        .filter(|child| child.kind() == "attribute")
+        .filter_map(|attribute| attribute.named_child(0).and_then(|name| node_text(&name, rope)))
+        .collect();
+    bindable.extend(components.iter()
+        .filter(|directive| directive.kind == ComponentKind::Directive)
+        .filter(|directive| attribute_selector(&directive.selector).is_some_and(|selector| attribute_names.iter()
+            .any(|attribute| parse_binding(attribute).map_or(attribute.as_str(), |(_, name)| name) == selector)))
+        .map(|directive| directive.clone()));
+    bindable
+}
+
+fn hover_contents(component: &Component) -> String {
+    let mut contents = format!("**{}** `<{}>`", component.class_name, component.selector);
+    if let Some(documentation) = &component.documentation {
+        contents.push_str(&format!("\n\n{}", documentation));
+    }
+    fn append_section(contents: &mut String, title: &str, elements: &[Property]) {
+        if !elements.is_empty() {
+            contents.push_str(&format!("\n\n{}:", title));
+            for element in elements {
+                contents.push_str(&format!("\n- `{}`", element.binding_name()));
+                if element.required {
+                    contents.push_str(" (required)");
                 }
-            });
-        }
+                // Only the summary line fits in a list
+                if let Some(summary) = element.documentation.as_deref().and_then(|documentation| documentation.lines().next()) {
+                    contents.push_str(&format!(" — {}", summary));
+                }
+            }
+        }
+    }
+    append_section(&mut contents, "Inputs", &component.inputs);
+    append_section(&mut contents, "Outputs", &component.outputs);
+    if !component.host_bindings.is_empty() {
+        contents.push_str("\n\nHost bindings:");
+        for binding in &component.host_bindings {
+            contents.push_str(&format!("\n- `{}`", binding));
+        }
+    }
+    let exported: Vec<String> = export_as::export_names(component).map(|name| format!("`{}`", name)).collect();
+    if !exported.is_empty() {
+        contents.push_str(&format!("\n\nExported as {}", exported.join(", ")));
+    }
+    contents
+}
+
+/// Extensions of the TypeScript sources that may declare components.
+static TYPESCRIPT_EXTENSIONS: &[&str] = &["ts", "mts", "cts", "tsx"];
+
+fn is_typescript(uri: &str) -> bool {
+    uri.rsplit_once('.').is_some_and(|(_, extension)| TYPESCRIPT_EXTENSIONS.contains(&extension))
+}
+
+/// Files matching the patterns of their workspace folder, along with their URL.
+fn files_matching(root_globs: &[(String, Vec<String>)]) -> Vec<(Url, PathBuf)> {
+    root_globs.iter()
+        .flat_map(|(root, globs)| ComponentAnalyzer::workspace_files(root, globs))
+        .filter_map(|path| Url::from_file_path(&path).ok().map(|uri| (uri, path)))
+        .collect()
+}
+
+/// Path of a workspace folder, decoded from its URI such that files can be matched to it.
+fn workspace_root(folder: &WorkspaceFolder) -> Option<String> {
+    folder.uri.to_file_path().ok().map(|path| path.to_string_lossy().into_owned())
+}
+
+/// Patterns of the TypeScript sources under `source_root`, relative to a workspace folder.
+fn typescript_globs(source_root: &str) -> Vec<String> {
+    TYPESCRIPT_EXTENSIONS.iter()
+        .map(|extension| format!("{}/**/*.{}", source_root.trim_end_matches('/'), extension))
+        .collect()
+}
+
+/// Patterns of the templates under `source_root` with one of the `extensions`, relative to a workspace folder.
+fn template_globs_under(source_root: &str, extensions: &[String]) -> Vec<String> {
+    extensions.iter()
+        .map(|extension| format!("{}/**/*.{}", source_root.trim_end_matches('/'), extension))
+        .collect()
+}
+
+async fn send_status(client: &Client, indexing: bool, components: &ComponentIndex) {
+    let status = Status { indexing, component_count: components.component_count() };
+    client.send_notification::<StatusNotification>(status).await;
+}
+
+/// How a document got parsed, for the logs.
+struct ParseSummary {
+    time: Duration,
+    /// Length of the document in bytes.
+    length: usize,
+    /// Ranges of the tree that an incremental parse changed.
+    changed_ranges: Option<Vec<tree_sitter::Range>>,
+}
+
+fn describe_parse(parse: Option<&ParseSummary>) -> String {
+    match parse {
+        Some(ParseSummary { time, length, changed_ranges: Some(ranges) }) => {
+            let changed: usize = ranges.iter().map(|range| range.end_byte - range.start_byte).sum();
+            format!(", parsed incrementally in {:?}, {} ranges changed ({} of {} bytes)", time, ranges.len(), changed, length)
+        }
+        Some(ParseSummary { time, .. }) => format!(", parsed in {:?}", time),
+        None => String::from(", not a template"),
+    }
+}
+
+fn document_diagnostics(uri: &str, document_map: &DashMap<String, Rope>, ast_map: &DashMap<String, Tree>,
+        template_map: &DashMap<String, Vec<InlineTemplate>>, components: &ComponentIndex, prefixes: &[String]) -> Option<Vec<Diagnostic>> {
+    if is_typescript(uri) {
+        let templates = template_map.get(uri)?;
+        let mut diagnostics: Vec<Diagnostic> = templates.iter()
+            .flat_map(|template| diagnostics::diagnostics(&template.tree.root_node(), &template.rope, components, Some(&template.owner), prefixes)
+                .into_iter()
+                .map(|diagnostic| Diagnostic { range: template.to_host_range(diagnostic.range), ..diagnostic }))
+            .collect();
+        if let Ok(uri) = Url::parse(uri) {
+            diagnostics.append(&mut diagnostics::duplicate_selectors(&uri, components));
+        }
+        return Some(diagnostics);
+    }
+    let rope = document_map.get(uri)?;
+    let ast = ast_map.get(uri)?;
+    let owner = Url::parse(uri).ok()
+        .and_then(|uri| components.template_owner(&uri));
+    Some(diagnostics::diagnostics(&ast.root_node(), &rope, components, owner.as_ref(), prefixes))
+}
+
+/// Recomputes diagnostics of every open template, e.g. once the index changed.
+async fn publish_all_diagnostics(client: &Client, document_map: &DashMap<String, Rope>, ast_map: &DashMap<String, Tree>,
+        template_map: &DashMap<String, Vec<InlineTemplate>>, components: &ComponentIndex, prefixes: &[String]) {
+    let uris: Vec<String> = document_map.iter()
+        .map(|document| document.key().clone())
+        .collect();
+    for uri in uris {
+        let diagnostics = document_diagnostics(&uri, document_map, ast_map, template_map, components, prefixes);
+        if let (Ok(uri), Some(diagnostics)) = (Url::parse(&uri), diagnostics) {
+            client.publish_diagnostics(uri, diagnostics, None).await;
+        }
+    }
+    // Component files that aren't open only have their duplicate selectors to report
+    for file_url in components.duplicate_files_to_report() {
+        if !document_map.contains_key(file_url.as_str()) {
+            let diagnostics = diagnostics::duplicate_selectors(&file_url, components);
+            client.publish_diagnostics(file_url, diagnostics, None).await;
+        }
+    }
+}
+
+/// Indexes the components of an open TypeScript document, unsaved changes included.
+fn index_document(uri: &Url, document_map: &DashMap<String, Rope>, components: &ComponentIndex) {
+    let Some(contents) = document_map.get(uri.as_str()).map(|rope| rope.to_string()) else {
+        return;
+    };
+    match ComponentAnalyzer::new() {
+        Some(mut analyzer) => analyzer.analyze_contents(&contents, uri, components),
+        None => error!("Error building analyzer"),
+    }
+}
+
+/// Extracts and parses the inline templates of a TypeScript document.
+fn update_inline_templates(uri: &Url, html_analyzer: &Mutex<HtmlAnalyzer>, document_map: &DashMap<String, Rope>,
+        template_map: &DashMap<String, Vec<InlineTemplate>>) {
+    let Some(rope) = document_map.get(uri.as_str()).map(|rope| rope.clone()) else {
+        return;
+    };
+    let Some(mut analyzer) = ComponentAnalyzer::new() else {
+        error!("Error building analyzer");
+        return;
+    };
+    let mut owners = analyzer.analyze_source(&rope.to_string(), uri);
+    // Components with several selectors share their template
+    owners.dedup_by(|owner, previous| owner.template_range == previous.template_range);
+    let templates = owners.into_iter()
+        .filter_map(|owner| {
+            let range = owner.template_range?;
+            let start = to_offset(&rope, range.start)?;
+            let end = to_offset(&rope, range.end)?;
+            let template_rope = Rope::from(rope.get_byte_slice(start..end)?);
+            Some(InlineTemplate {
+                start,
+                start_position: range.start,
+                owner,
+                tree: html_analyzer.lock().unwrap().parse(&template_rope, None),
+                rope: template_rope,
+            })
+        })
+        .collect();
+    template_map.insert(uri.to_string(), templates);
+}
+
+#[allow(deprecated)]
+fn component_symbol(component: &Component) -> DocumentSymbol {
+    fn property_symbol(property: &Property, kind: SymbolKind) -> DocumentSymbol {
+        DocumentSymbol {
+            name: property.name.clone(),
+            detail: property.alias.clone(),
+            kind,
+            tags: None,
+            deprecated: None,
+            range: property.range,
+            selection_range: property.range,
+            children: None,
+        }
+    }
+    let children = component.inputs.iter()
+        .map(|input| property_symbol(input, SymbolKind::FIELD))
+        .chain(component.outputs.iter().map(|output| property_symbol(output, SymbolKind::EVENT)))
+        .collect();
+    DocumentSymbol {
+        name: component.class_name.clone(),
+        detail: Some(component.selector.clone()),
+        kind: SymbolKind::CLASS,
+        tags: None,
+        deprecated: None,
+        range: component.declaration_range,
+        selection_range: component.class_name_range,
+        children: Some(children),
+    }
+}
+
+struct Backend {
+    client: Client,
+    config: RwLock<Config>,
+    components: Arc<ComponentIndex>,
+    /// Shared by every document, parsing being short and never awaited on.
+    html_analyzer: Arc<Mutex<HtmlAnalyzer>>,
+    /// Paths of the workspace folders, which indexing workers check as folders get removed.
+    workspace_roots: Arc<RwLock<Vec<String>>>,
+    /// Whether the client shows server initiated progress.
+    work_done_progress: AtomicBool,
+    document_map: Arc<DashMap<String, Rope>>,
+    /// HTML trees of the template documents only, by extension: handlers take
+    /// every tree in there for HTML, TypeScript documents never get one.
+    ast_map: Arc<DashMap<String, Tree>>,
+    /// Inline templates of the TypeScript documents, whose components are indexed instead.
+    template_map: Arc<DashMap<String, Vec<InlineTemplate>>>,
+    /// Bumped on every change of a document to drop the stale pending analyses.
+    generations: Arc<DashMap<String, usize>>,
+    /// Bumped by every full re-indexing to stop the indexing tasks it supersedes.
+    index_generation: Arc<AtomicUsize>,
+    /// Indexing tasks running, as folders may be indexed while others still are.
+    indexing_tasks: Arc<AtomicUsize>,
+    /// Versions of the open documents, as the client numbers them.
+    versions: DashMap<String, i32>,
+    /// Last completions of each document, reused while nothing they depend on changed.
+    completion_cache: Arc<DashMap<String, (CompletionKey, Vec<CompletionItem>)>>,
+    /// Number of elements of each name in the workspace template files, as last read from disk.
+    template_file_usages: DashMap<Url, HashMap<String, usize>>,
+    /// Whether `template_file_usages` was filled since the last reindexing.
+    template_files_counted: AtomicBool,
+    /// Selectors the lazy indexing didn't find, until a TypeScript file is created or changed.
+    lazy_misses: DashSet<String>,
+}
+
+/// What completions depend on: the text, the position and the index.
+/// Inline templates are only reparsed on analysis, which drops the cached completions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CompletionKey {
+    version: i32,
+    position: Position,
+    revision: usize,
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        *self.config.write().unwrap() = Config::from_options(params.initialization_options);
+        let work_done_progress = params.capabilities.window
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false);
+        self.work_done_progress.store(work_done_progress, Ordering::Relaxed);
+        if let Some(workspaces) = params.workspace_folders {
+            self.workspace_roots.write().unwrap()
+                .extend(workspaces.iter().filter_map(workspace_root));
+        }
         Ok(InitializeResult {
             server_info: None,
+            offset_encoding: None,
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::INCREMENTAL),
+                    will_save: None,
+                    will_save_wait_until: Some(true),
+                    save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                })),
                 workspace: Some(WorkspaceServerCapabilities {
                     workspace_folders: Some(WorkspaceFoldersServerCapabilities {
                         supported: Some(true),
@@ -258,133 +1453,1359 @@ impl LanguageServer for Backend {
                     file_operations: None,
                 }),
                 definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
                 completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
-                    trigger_characters: None,
+                    resolve_provider: Some(true),
+                    trigger_characters: Some(["<", "[", "(", "*", " ", "@"].iter().map(|c| String::from(*c)).collect()),
                     work_done_progress_options: Default::default(),
                     all_commit_characters: None,
+                    completion_item: None,
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(false) }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![String::from(REINDEX_COMMAND), String::from(GOTO_OWNING_COMPONENT_COMMAND),
+                        String::from(FIND_UNUSED_COMPONENTS_COMMAND), String::from(code_lens::SHOW_REFERENCES_COMMAND)],
+                    work_done_progress_options: Default::default(),
+                }),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: String::from(binding_brackets::FIRST_TRIGGER_CHARACTER),
+                    more_trigger_character: Some(binding_brackets::MORE_TRIGGER_CHARACTERS.iter().map(|c| String::from(*c)).collect()),
+                }),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![String::from("(")]),
+                    retrigger_characters: None,
+                    work_done_progress_options: Default::default(),
                 }),
+                semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    range: None,
+                    work_done_progress_options: Default::default(),
+                })),
                 ..ServerCapabilities::default()
             },
         })
     }
 
     async fn initialized(&self, _: InitializedParams) {
-        self.client
-            .log_message(MessageType::INFO, "initialized!")
-            .await;
+        // Indexing waits for the client to be ready to show its progress
+        let roots = self.workspace_roots.read().unwrap().clone();
+        self.log(LogLevel::Info, format!("Initialized with workspace folders {:?}", roots)).await;
+        if let Err(e) = self.client.register_capability(vec![pull_diagnostics::registration(), type_hierarchy::registration()]).await {
+            warn!("Error registering pull diagnostics and type hierarchy {:?}", e);
+        }
+        if self.config.read().unwrap().index_on_startup {
+            self.index_folders(roots);
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
 
-    async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
-        self.client
-            .log_message(MessageType::INFO, "workspace folders changed!")
-            .await;
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let uris = |folders: &[WorkspaceFolder]| folders.iter().map(|folder| folder.uri.to_string()).collect::<Vec<_>>();
+        self.log(LogLevel::Info, format!("Workspace folders added {:?}, removed {:?}",
+            uris(&params.event.added), uris(&params.event.removed))).await;
+        for folder in params.event.removed {
+            if let Some(removed) = workspace_root(&folder) {
+                self.workspace_roots.write().unwrap().retain(|root| Path::new(root) != Path::new(&removed));
+            }
+            self.components.remove_folder(&folder.uri);
+        }
+        publish_all_diagnostics(&self.client, &self.document_map, &self.ast_map, &self.template_map, &self.components, &self.component_prefixes()).await;
+        let added: Vec<String> = params.event.added.iter()
+            .filter_map(workspace_root)
+            .collect();
+        self.workspace_roots.write().unwrap().extend(added.iter().cloned());
+        self.lazy_misses.clear();
+        if self.config.read().unwrap().index_on_startup {
+            self.index_folders(added);
+        }
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        match params.command.as_str() {
+            REINDEX_COMMAND => {
+                // Indexing tasks still running would only slow this one down
+                self.index_generation.fetch_add(1, Ordering::SeqCst);
+                self.components.clear();
+                self.template_files_counted.store(false, Ordering::SeqCst);
+                self.lazy_misses.clear();
+                let roots = self.workspace_roots.read().unwrap().clone();
+                // Analyzing every file again, whatever the saved index says
+                self.indexing(roots, false).await;
+                Ok(Some(json!({ "components": self.components.component_count() })))
+            }
+            GOTO_OWNING_COMPONENT_COMMAND => {
+                let Some(uri) = params.arguments.first().and_then(|argument| argument.as_str()) else {
+                    return Err(Error::invalid_params("Expected the URI of the template"));
+                };
+                let uri = Url::parse(uri).map_err(|e| Error::invalid_params(format!("Invalid URI {}: {}", uri, e)))?;
+                Ok(self.components.template_owner(&uri)
+                    .map(|component| json!(Location::new(component.file_url.clone(), component.class_name_range))))
+            }
+            FIND_UNUSED_COMPONENTS_COMMAND => {
+                let mut names = HashSet::new();
+                self.find_in_templates(&mut |root, rope| {
+                    usages::used_names(root, rope, &mut names);
+                    Vec::new()
+                });
+                let mut unused: Vec<UnusedComponent> = self.components.iter()
+                    .filter(|component| usages::is_used(component, &names) == Some(false))
+                    .map(|component| UnusedComponent {
+                        selector: component.selector.clone(),
+                        class_name: component.class_name.clone(),
+                        location: Location::new(component.file_url.clone(), component.class_name_range),
+                    })
+                    .collect();
+                unused.sort_by(|a, b| a.selector.cmp(&b.selector));
+                Ok(Some(json!(unused)))
+            }
+            code_lens::SHOW_REFERENCES_COMMAND => {
+                let uri = params.arguments.first().and_then(|argument| serde_json::from_value::<Url>(argument.clone()).ok());
+                let position = params.arguments.get(1).and_then(|argument| serde_json::from_value::<Position>(argument.clone()).ok());
+                let (Some(uri), Some(position)) = (uri, position) else {
+                    return Err(Error::invalid_params("Expected the URI of the document and a position"));
+                };
+                let locations = self.references(ReferenceParams {
+                    text_document_position: TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri), position),
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                    context: ReferenceContext { include_declaration: false },
+                }).await?;
+                Ok(Some(json!(locations.unwrap_or_default())))
+            }
+            command => Err(Error::invalid_params(format!("Unknown command {}", command))),
+        }
     }
 
     async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
-        let mut message = String::from("watched: ");
-        message.push_str(
-            params
-                .changes
-                .iter()
-                .map(|x| x.uri.to_string())
-                .collect::<Vec<_>>()
-                .join("|")
-                .as_str(),
-        );
-        self.client.log_message(MessageType::INFO, message).await;
+        let changes: Vec<String> = params.changes.iter()
+            .map(|change| format!("{:?} {}", change.typ, change.uri))
+            .collect();
+        self.log(LogLevel::Debug, format!("Watched files changed: {}", changes.join(", "))).await;
+
+        // Source roots may have moved, leaving no part of the index reliable
+        if params.changes.iter().any(|change| change.uri.path().ends_with(&format!("/{}", angular_json::FILE_NAME))) {
+            self.log(LogLevel::Info, format!("{} changed, clearing the index", angular_json::FILE_NAME)).await;
+            self.index_generation.fetch_add(1, Ordering::SeqCst);
+            self.components.clear();
+            self.lazy_misses.clear();
+            self.template_files_counted.store(false, Ordering::SeqCst);
+            if self.config.read().unwrap().index_on_startup {
+                let roots = self.workspace_roots.read().unwrap().clone();
+                self.index_folders(roots);
+            } else {
+                publish_all_diagnostics(&self.client, &self.document_map, &self.ast_map, &self.template_map, &self.components, &self.component_prefixes()).await;
+            }
+            return;
+        }
+
+        let Some(mut analyzer) = ComponentAnalyzer::new() else {
+            error!("Error building analyzer");
+            return;
+        };
+        for change in params.changes.iter() {
+            if change.typ == FileChangeType::DELETED {
+                self.components.remove_file(&change.uri);
+                self.forget_document(&change.uri);
+                self.template_file_usages.remove(&change.uri);
+                self.client.publish_diagnostics(change.uri.clone(), Vec::new(), None).await;
+            } else if !is_typescript(change.uri.as_str()) {
+                // New or edited template files count once all of them are counted again
+                if self.config.read().unwrap().is_template(change.uri.as_str()) {
+                    self.template_files_counted.store(false, Ordering::SeqCst);
+                }
+            } else if let Ok(path) = change.uri.to_file_path() {
+                // The created or changed file may declare a selector missed so far
+                self.lazy_misses.clear();
+                if let Err(e) = analyzer.analyze_file(&path, &self.document_map, &self.components, self.max_file_size()) {
+                    warn!("Error analyzing file {:?} {:?}", path, e);
+                }
+            }
+        }
+        publish_all_diagnostics(&self.client, &self.document_map, &self.ast_map, &self.template_map, &self.components, &self.component_prefixes()).await;
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        let definition = || -> Option<GotoDefinitionResponse> {
-            let uri = params.text_document_position_params.text_document.uri;
-            let ast = self.ast_map.get(&uri.to_string())?;
-            let rope = self.document_map.get(&uri.to_string())?;
-
-            let position = params.text_document_position_params.position;
-            let line_position = rope.try_line_to_char(position.line as usize).ok()?;
-            let offset = line_position + position.character as usize;
-            
-            let node = find_node(&ast.root_node(), offset, vec!["tag_name"])?;
-            let tag_name = rope.slice(node.start_byte()..node.end_byte()).as_str()?;
-            let component = self.components.get(tag_name)?;
-            return Some(GotoDefinitionResponse::Scalar(Location::new(component.file_url.clone(), component.class_name_range)));
-        }();
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let tag_name = self.with_template(&uri, position, |root, offset, rope| {
+            let tag = find_node(root, offset, vec!["start_tag", "self_closing_tag", "end_tag"])?;
+            node_text(&tag.named_child(0)?, rope)
+        });
+        if let Some(tag_name) = tag_name {
+            self.index_lazily(&tag_name).await;
+        }
+        let definition = self.with_template(&uri, position, |root, offset, rope| {
+            if let Some((name, _)) = expression::pipe_at(root, offset, rope) {
+                let pipe = self.components.pipe(&name)?;
+                return Some(GotoDefinitionResponse::Scalar(Location::new(pipe.file_url.clone(), pipe.class_name_range)));
+            }
+            if let Some(directive) = export_as::exported_at(root, offset, rope, &self.components) {
+                return Some(GotoDefinitionResponse::Scalar(Location::new(directive.file_url, directive.class_name_range)));
+            }
+            let node = find_node(root, offset, vec!["tag_name", "attribute_name"])?;
+            if node.kind() == "attribute_name" {
+                return self.attribute_definition(&node, rope);
+            }
+            let tag_name = node_text(&node, rope)?;
+            // Several components with the same selector let the user pick one
+            let mut locations: Vec<Location> = self.components.declarations(&tag_name).iter()
+                .map(|component| Location::new(component.file_url.clone(), component.class_name_range))
+                .collect();
+            match locations.len() {
+                0 => None,
+                1 => Some(GotoDefinitionResponse::Scalar(locations.remove(0))),
+                _ => Some(GotoDefinitionResponse::Array(locations)),
+            }
+        });
         Ok(definition)
     }
 
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let selector = self.selector_at(&uri, position)
+            .map(|(selector, _)| selector)
+            .or_else(|| self.components.iter()
+                .find(|component| component.file_url == uri && range_contains(&component.class_name_range, position))
+                .map(|component| component.selector.clone()));
+        let Some(selector) = selector else {
+            return Ok(None);
+        };
+        self.index_lazily(&selector).await;
+
+        let mut locations = Vec::new();
+        if params.context.include_declaration {
+            locations.extend(self.components.declarations(&selector).iter()
+                .map(|component| Location::new(component.file_url.clone(), component.class_name_range)));
+        }
+        locations.append(&mut self.find_references(&selector));
+        Ok(Some(locations))
+    }
+
+    async fn prepare_rename(&self, params: TextDocumentPositionParams) -> Result<Option<PrepareRenameResponse>> {
+        let uri = params.text_document.uri;
+        let prepare = self.selector_at(&uri, params.position)
+            .filter(|(selector, _)| self.components.get(selector)
+                .is_some_and(|component| component.kind == ComponentKind::Component))
+            .map(|(_, range)| range)
+            .or_else(|| self.property_target(&uri, params.position).map(|(_, range)| range))
+            .map(PrepareRenameResponse::Range);
+        Ok(prepare)
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let new_name = params.new_name;
+        if self.selector_at(&uri, position).is_none() {
+            if let Some((target, _)) = self.property_target(&uri, position) {
+                return self.rename_property(&target, &new_name);
+            }
+        }
+        if !is_valid_selector(&new_name) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!("'{}' is not a valid element selector", new_name)));
+        }
+        let Some((selector, _)) = self.selector_at(&uri, position) else {
+            return Ok(None);
+        };
+        let Some(declaration) = self.components.get(&selector)
+            .filter(|component| component.kind == ComponentKind::Component)
+            .map(|component| Location::new(component.file_url.clone(), component.selector_range)) else {
+            return Ok(None);
+        };
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for location in std::iter::once(declaration).chain(self.find_references(&selector)) {
+            changes.entry(location.uri)
+                .or_default()
+                .push(TextEdit::new(location.range, new_name.clone()));
+        }
+        for edits in changes.values_mut() {
+            edits.sort_by_key(|edit| edit.range.start);
+            edits.dedup_by_key(|edit| edit.range);
+        }
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    async fn document_symbol(&self, params: DocumentSymbolParams) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri;
+        if !is_typescript(uri.as_str()) {
+            let (Some(rope), Some(ast)) = (self.document_map.get(uri.as_str()), self.ast_map.get(uri.as_str())) else {
+                return Ok(None);
+            };
+            let symbols = template_symbols::element_symbols(&ast.root_node(), &rope, &self.components);
+            return Ok(Some(DocumentSymbolResponse::Nested(symbols)));
+        }
+        let contents = match self.document_map.get(&uri.to_string()) {
+            Some(rope) => Some(rope.to_string()),
+            None => uri.to_file_path().ok().and_then(|path| fs::read_to_string(path).ok()),
+        };
+        let (Some(contents), Some(mut analyzer)) = (contents, ComponentAnalyzer::new()) else {
+            return Ok(None);
+        };
+        let mut components = analyzer.analyze_source(&contents, &uri);
+        // A class with several selectors is a single symbol
+        components.dedup_by(|component, previous| component.class_name_range == previous.class_name_range);
+        let symbols = components.iter()
+            .map(component_symbol)
+            .collect();
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    #[allow(deprecated)]
+    async fn symbol(&self, params: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
+        let query = params.query.to_lowercase();
+        let mut symbols: Vec<SymbolInformation> = self.components.iter()
+            .filter(|component| component.selector.to_lowercase().contains(&query))
+            .map(|component| SymbolInformation {
+                name: component.selector.clone(),
+                kind: SymbolKind::CLASS,
+                tags: None,
+                deprecated: None,
+                location: Location::new(component.file_url.clone(), component.class_name_range),
+                container_name: Some(component.class_name.clone()),
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name));
+        symbols.truncate(MAX_WORKSPACE_SYMBOLS);
+        Ok(Some(symbols))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let hover = self.with_template(&uri, position, |root, offset, rope| {
+            let directive_docs = find_node(root, offset, vec!["attribute_name"])
+                .and_then(|attribute_name| microsyntax::hover_contents(&node_text(&attribute_name, rope)?));
+            if let Some(contents) = directive_docs {
+                return Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: contents }),
+                    range: None,
+                });
+            }
+            let component = match export_as::exported_at(root, offset, rope, &self.components) {
+                Some(directive) => directive,
+                None => {
+                    let node = find_node(root, offset, vec!["tag_name"])?;
+                    let tag_name = node_text(&node, rope)?;
+                    self.components.get(&tag_name)?.clone()
+                }
+            };
+            Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: hover_contents(&component),
+                }),
+                range: None,
+            })
+        });
+        Ok(hover)
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         let uri = params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
-        let completions = || -> Option<Vec<CompletionItem>> {
-            let rope = self.document_map.get(&uri.to_string())?;
-            let ast = self.ast_map.get(&uri.to_string())?;
-            let char = rope.try_line_to_char(position.line as usize).ok()?;
-            let offset = char + position.character as usize;
-            return Some(completion(&ast.root_node(), offset, &rope, &self.components));
-        }();
+        let key = self.versions.get(uri.as_str()).map(|version| CompletionKey {
+            version: *version,
+            position,
+            revision: self.components.revision(),
+        });
+        let cached = self.completion_cache.get(uri.as_str())
+            .filter(|cached| Some(cached.0) == key)
+            .map(|cached| cached.1.clone());
+        if let Some(completions) = cached {
+            self.log(LogLevel::Debug, format!("Reused the completions of {} at {}:{}", uri, position.line, position.character)).await;
+            return Ok(Some(CompletionResponse::Array(completions)));
+        }
+        let roots = self.workspace_roots.read().unwrap().clone();
+        let owner = self.components.template_owner(&uri)
+            .or_else(|| self.inline_template_owner(&uri, position));
+        let completions = self.with_template(&uri, position, |root, offset, rope| {
+            Some(completion::completion(root, offset, rope, &self.components, owner.as_ref(), &roots))
+        });
+        if let (Some(key), Some(completions)) = (key, &completions) {
+            self.completion_cache.insert(uri.to_string(), (key, completions.clone()));
+        }
         Ok(completions.map(CompletionResponse::Array))
     }
 
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        Ok(self.with_template(&uri, position, |root, offset, rope| {
+            signature_help::signature_help(root, offset, rope, &self.components)
+        }))
+    }
+
+    async fn document_highlight(&self, params: DocumentHighlightParams) -> Result<Option<Vec<DocumentHighlight>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let highlights = self.with_template(&uri, position, |root, offset, rope| {
+            let tag_name = find_node(root, offset, vec!["tag_name"])?;
+            let selector = node_text(&tag_name, rope)?;
+            // The opening and closing tags of the element under the cursor
+            let element = tag_name.parent()?.parent()?;
+            let mut cursor = element.walk();
+            let tag_ranges: Vec<Range> = element.named_children(&mut cursor)
+                .filter(|tag| matches!(tag.kind(), "start_tag" | "end_tag" | "self_closing_tag"))
+                .filter_map(|tag| tag.named_child(0))
+                .map(|tag_name| to_range(&*rope, tag_name.range()))
+                .collect();
+            let mut highlights: Vec<DocumentHighlight> = tag_ranges.iter()
+                .map(|range| DocumentHighlight { range: *range, kind: Some(DocumentHighlightKind::READ) })
+                .collect();
+            highlights.extend(find_tag_references(root, rope, &selector).into_iter()
+                .filter(|range| !tag_ranges.contains(range))
+                .map(|range| DocumentHighlight { range, kind: Some(DocumentHighlightKind::TEXT) }));
+            Some(highlights)
+        });
+        Ok(highlights.map(|highlights| highlights.into_iter()
+            .map(|highlight| DocumentHighlight {
+                range: self.to_document_range(&uri, position, highlight.range),
+                ..highlight
+            })
+            .collect()))
+    }
+
+    async fn linked_editing_range(&self, params: LinkedEditingRangeParams) -> Result<Option<LinkedEditingRanges>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let ranges = self.with_template(&uri, position, |root, offset, rope| {
+            let tag_name = find_node(root, offset, vec!["tag_name"])?;
+            // Self-closing tags and unclosed elements have no other name to edit along
+            let element = tag_name.parent()?.parent()?;
+            let tag_range = |kind| {
+                let mut cursor = element.walk();
+                let tag = element.named_children(&mut cursor).find(|child| child.kind() == kind)?;
+                Some(to_range(rope, tag.named_child(0)?.range()))
+            };
+            Some(vec![tag_range("start_tag")?, tag_range("end_tag")?])
+        });
+        Ok(ranges.map(|ranges| LinkedEditingRanges {
+            ranges: ranges.into_iter().map(|range| self.to_document_range(&uri, position, range)).collect(),
+            word_pattern: Some(String::from(r"[a-zA-Z][\w:.-]*")),
+        }))
+    }
+
+    async fn on_type_formatting(&self, params: DocumentOnTypeFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let edits = self.with_template(&uri, position, |root, offset, rope| {
+            Some(binding_brackets::closing_edits(root, offset, rope, &params.ch))
+        });
+        Ok(edits.map(|edits| edits.into_iter()
+            .map(|edit| TextEdit { range: self.to_document_range(&uri, position, edit.range), ..edit })
+            .collect()))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let uri = params.text_document.uri;
+        if is_typescript(uri.as_str()) {
+            return Ok(None);
+        }
+        let ranges = self.ast_map.get(&uri.to_string())
+            .map(|ast| folding_ranges::folding_ranges(&ast.root_node()));
+        Ok(ranges)
+    }
+
+    async fn selection_range(&self, params: SelectionRangeParams) -> Result<Option<Vec<SelectionRange>>> {
+        let uri = params.text_document.uri;
+        if is_typescript(uri.as_str()) {
+            return Ok(None);
+        }
+        let (Some(rope), Some(ast)) = (self.document_map.get(uri.as_str()), self.ast_map.get(uri.as_str())) else {
+            return Ok(None);
+        };
+        // Every position needs a range, if only an empty one
+        let ranges = params.positions.iter()
+            .map(|position| to_offset(&rope, *position)
+                .and_then(|offset| selection_ranges::selection_range(&ast.root_node(), offset, &rope))
+                .unwrap_or(SelectionRange { range: Range::new(*position, *position), parent: None }))
+            .collect();
+        Ok(Some(ranges))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri.to_string();
+        if !self.document_map.contains_key(&uri) {
+            return Ok(None);
+        }
+        let usages = self.usage_counts().await;
+        let mut lenses = Vec::new();
+        if let Some(templates) = self.template_map.get(&uri) {
+            for template in templates.iter() {
+                lenses.extend(code_lens::binding_lenses(&template.tree.root_node(), &template.rope, &self.components, &usages).into_iter()
+                    .map(|lens| CodeLens { range: template.to_host_range(lens.range), ..lens }));
+            }
+        } else if let (Some(rope), Some(ast)) = (self.document_map.get(&uri), self.ast_map.get(&uri)) {
+            lenses = code_lens::binding_lenses(&ast.root_node(), &rope, &self.components, &usages);
+        } else {
+            return Ok(None);
+        }
+        Ok(Some(lenses.into_iter()
+            .map(|lens| code_lens::with_location(lens, &params.text_document.uri))
+            .collect()))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = &params.text_document.uri;
+        let owner = self.components.template_owner(uri)
+            .or_else(|| self.inline_template_owner(uri, params.range.start));
+        let Some(scope) = owner.and_then(|owner| ImportScope::of(&owner, &self.components)) else {
+            return Ok(None);
+        };
+        let Some(contents) = self.document_contents(scope.file_url()) else {
+            return Ok(None);
+        };
+        let missing_import = NumberOrString::String(String::from(diagnostics::MISSING_IMPORT));
+        let actions = params.context.diagnostics.iter()
+            .filter(|diagnostic| diagnostic.code.as_ref() == Some(&missing_import))
+            .filter_map(|diagnostic| {
+                let selector = diagnostic.data.as_ref()?.as_str()?;
+                let component = self.components.get(selector)?.clone();
+                let (class_name, edit) = scope.import_edit(&component, &contents, &self.components)?;
+                Some(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("Import {}", class_name),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(edit),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }))
+            })
+            .collect();
+        Ok(Some(actions))
+    }
+
+    async fn semantic_tokens_full(&self, params: SemanticTokensParams) -> Result<Option<SemanticTokensResult>> {
+        let uri = params.text_document.uri.to_string();
+        let mut tokens = Vec::new();
+        if let Some(templates) = self.template_map.get(&uri) {
+            for template in templates.iter() {
+                tokens.extend(semantic_tokens::template_tokens(&template.tree.root_node(), &template.rope).into_iter()
+                    .map(|(range, token_type)| (template.to_host_range(range), token_type)));
+            }
+        } else if let (Some(rope), Some(ast)) = (self.document_map.get(&uri), self.ast_map.get(&uri)) {
+            tokens = semantic_tokens::template_tokens(&ast.root_node(), &rope);
+        } else {
+            return Ok(None);
+        }
+        tokens.sort_by_key(|(range, _)| range.start);
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: semantic_tokens::encode(&tokens),
+        })))
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        Ok(completion::resolve(item, &self.components))
+    }
+
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.client
-            .log_message(MessageType::INFO, "file opened!")
-            .await;
-        self.on_change(TextDocumentItem {
-            uri: params.text_document.uri,
-            text: params.text_document.text,
-        })
-        .await
+        let uri = params.text_document.uri;
+        let rope = Rope::from_str(&params.text_document.text);
+        let lines = rope.len_lines();
+        let parse = self.update_document(TextDocumentItem {
+            uri: uri.clone(),
+            version: params.text_document.version,
+            rope,
+            old_tree: None,
+        });
+        self.log(LogLevel::Debug, format!("Opened {} ({} lines){}", uri, lines, describe_parse(parse.as_ref()))).await;
+        self.analyze_now(uri).await
     }
 
-    async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
-        self.client
-            .log_message(MessageType::INFO, "file changed!")
-            .await;
-        self.on_change(TextDocumentItem {
-            uri: params.text_document.uri,
-            text: std::mem::take(&mut params.content_changes[0].text),
-        })
-        .await
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        // Like an empty insertion, nothing that would need a new analysis
+        let changes: Vec<TextDocumentContentChangeEvent> = params.content_changes.into_iter()
+            .filter(|change| !change.text.is_empty() || change.range.is_none_or(|range| range.start != range.end))
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        let mut rope = self.document_map.get(&uri.to_string())
+            .map(|rope| rope.clone())
+            .unwrap_or_default();
+        let mut old_tree = self.ast_map.get(&uri.to_string()).map(|tree| tree.clone());
+        let old_rope = rope.clone();
+        let change_count = changes.len();
+        let mut edits = Vec::new();
+        let mut edits_blocks = false;
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let (edit, edit_blocks) = apply_change(&mut rope, range, &change.text);
+                    edits.push(edit);
+                    edits_blocks |= edit_blocks;
+                }
+                None => {
+                    rope = Rope::from_str(&change.text);
+                    old_tree = None;
+                }
+            }
+        }
+        if let Some(tree) = old_tree.as_mut() {
+            // The tree was parsed from the masked text, which may then change beyond the edited ranges
+            if edits_blocks {
+                if let Some(edit) = control_flow::masked_edit(&old_rope.to_string(), &rope.to_string()) {
+                    tree.edit(&edit);
+                }
+            } else {
+                for edit in &edits {
+                    tree.edit(edit);
+                }
+            }
+        }
+        let parse = self.update_document(TextDocumentItem {
+            uri: uri.clone(),
+            version: params.text_document.version,
+            rope,
+            old_tree,
+        });
+        self.log(LogLevel::Debug, format!("Changed {} ({} changes){}", uri, change_count, describe_parse(parse.as_ref()))).await;
+        self.analyze_later(uri)
     }
 
-    async fn did_save(&self, _: DidSaveTextDocumentParams) {
-        self.client
-            .log_message(MessageType::INFO, "file saved!")
-            .await;
+    async fn will_save_wait_until(&self, params: WillSaveTextDocumentParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.to_string();
+        let mut edits = Vec::new();
+        if let Some(templates) = self.template_map.get(&uri) {
+            for template in templates.iter() {
+                edits.extend(save_formatting::tidy_bindings(&template.tree.root_node(), &template.rope, &self.components).into_iter()
+                    .map(|edit| TextEdit { range: template.to_host_range(edit.range), ..edit }));
+            }
+        } else if let (Some(rope), Some(ast)) = (self.document_map.get(&uri), self.ast_map.get(&uri)) {
+            edits = save_formatting::tidy_bindings(&ast.root_node(), &rope, &self.components);
+        }
+        Ok(Some(edits))
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+        // The saved content wins over the buffer, when the client sends it
+        if let Some(text) = params.text {
+            let stale = self.document_map.get(uri.as_str()).is_none_or(|rope| *rope != text);
+            if stale {
+                let version = self.versions.get(uri.as_str()).map(|version| *version).unwrap_or_default();
+                self.update_document(TextDocumentItem {
+                    uri: uri.clone(),
+                    version,
+                    rope: Rope::from_str(&text),
+                    old_tree: None,
+                });
+            }
+        }
+        // What the template file now counts, once it's closed
+        if let (Some(rope), Some(ast)) = (self.document_map.get(uri.as_str()), self.ast_map.get(uri.as_str())) {
+            if self.template_file_usages.contains_key(&uri) {
+                let mut counts = HashMap::new();
+                usages::element_counts(&ast.root_node(), &rope, &mut counts);
+                self.template_file_usages.insert(uri.clone(), counts);
+            }
+        }
+        self.log(LogLevel::Debug, format!("Saved {}", uri)).await;
+        // A fresh pass, whatever the pending analysis of the last changes
+        self.analyze_now(uri).await
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.document_map.remove(&params.text_document.uri.to_string());
-        self.client
-            .log_message(MessageType::INFO, "file closed!")
-            .await;
+        let uri = params.text_document.uri;
+        self.forget_document(&uri);
+        self.client.publish_diagnostics(uri.clone(), Vec::new(), None).await;
+        // Unsaved changes of a component are gone with its document
+        if let (true, Ok(path), Some(mut analyzer)) = (is_typescript(uri.as_str()), uri.to_file_path(), ComponentAnalyzer::new()) {
+            if let Err(e) = analyzer.analyze_file(&path, &self.document_map, &self.components, self.max_file_size()) {
+                warn!("Error analyzing file {:?} {:?}", path, e);
+            }
+            publish_all_diagnostics(&self.client, &self.document_map, &self.ast_map, &self.template_map, &self.components, &self.component_prefixes()).await;
+        }
+        self.log(LogLevel::Debug, format!("Closed {}", uri)).await;
     }
 }
 
 struct TextDocumentItem {
     uri: Url,
-    text: String,
+    version: i32,
+    rope: Rope,
+    /// Previous tree of the document, already edited to match `rope`.
+    old_tree: Option<Tree>,
 }
 
 impl Backend {
-    async fn on_change(&self, params: TextDocumentItem) {
-        let rope = ropey::Rope::from_str(&params.text);
+    fn new(client: Client) -> Backend {
+        Backend {
+            client,
+            config: RwLock::new(Config::default()),
+            components: Arc::new(ComponentIndex::default()),
+            html_analyzer: Arc::new(Mutex::new(HtmlAnalyzer::new())),
+            workspace_roots: Arc::new(RwLock::new(Vec::new())),
+            work_done_progress: AtomicBool::new(false),
+            ast_map: Arc::new(DashMap::new()),
+            document_map: Arc::new(DashMap::new()),
+            template_map: Arc::new(DashMap::new()),
+            generations: Arc::new(DashMap::new()),
+            index_generation: Arc::new(AtomicUsize::new(0)),
+            indexing_tasks: Arc::new(AtomicUsize::new(0)),
+            versions: DashMap::new(),
+            completion_cache: Arc::new(DashMap::new()),
+            template_file_usages: DashMap::new(),
+            template_files_counted: AtomicBool::new(false),
+            lazy_misses: DashSet::new(),
+        }
+    }
+
+    /// Handles `textDocument/inlayHint`, which the `LanguageServer` trait doesn't know yet.
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = params.text_document.uri.to_string();
+        let mut hints = Vec::new();
+        if let Some(templates) = self.template_map.get(&uri) {
+            for template in templates.iter() {
+                hints.extend(inlay_hints::input_type_hints(&template.tree.root_node(), &template.rope, &self.components).into_iter()
+                    .map(|hint| InlayHint {
+                        position: template.to_host_position(hint.position),
+                        ..hint
+                    }));
+            }
+        } else if let (Some(rope), Some(ast)) = (self.document_map.get(&uri), self.ast_map.get(&uri)) {
+            hints = inlay_hints::input_type_hints(&ast.root_node(), &rope, &self.components);
+        } else {
+            return Ok(None);
+        }
+        hints.retain(|hint| range_contains(&params.range, hint.position));
+        Ok(Some(hints))
+    }
+
+    /// Handles `textDocument/diagnostic`, answering the diagnostics that would be published,
+    /// or that the previous report still holds while neither the document nor the index changed.
+    async fn document_diagnostic(&self, params: DocumentDiagnosticParams) -> Result<DocumentDiagnosticReport> {
+        let uri = params.text_document.uri;
+        let result_id = self.generations.get(uri.as_str())
+            .map(|generation| format!("{}-{}", *generation, self.components.revision()));
+        if let Some(result_id) = result_id.clone().filter(|result_id| params.previous_result_id.as_ref() == Some(result_id)) {
+            return Ok(DocumentDiagnosticReport::Unchanged { result_id });
+        }
+        let items = document_diagnostics(uri.as_str(), &self.document_map, &self.ast_map, &self.template_map, &self.components, &self.component_prefixes())
+            .unwrap_or_default();
+        Ok(DocumentDiagnosticReport::Full { result_id, items })
+    }
+
+    /// Handles `textDocument/prepareTypeHierarchy`, from the declaration of a component or its tag in a template.
+    async fn prepare_type_hierarchy(&self, params: TypeHierarchyPrepareParams) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let uri = params.text_document.uri;
+        let declared = self.components.iter()
+            .find(|component| component.file_url == uri && range_contains(&component.declaration_range, params.position))
+            .map(|component| component.clone());
+        let component = declared.or_else(|| self.selector_at(&uri, params.position)
+            .and_then(|(selector, _)| self.components.get(&selector).map(|component| component.clone())));
+        Ok(component.map(|component| vec![type_hierarchy::component_item(&component)]))
+    }
+
+    async fn type_hierarchy_supertypes(&self, params: TypeHierarchyItemParams) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(Some(type_hierarchy::supertypes(&params.item, &self.components)))
+    }
+
+    async fn type_hierarchy_subtypes(&self, params: TypeHierarchyItemParams) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        Ok(Some(type_hierarchy::subtypes(&params.item, &self.components)))
+    }
+
+    /// Handles the custom `angular/componentIndex` request, mostly useful for debugging.
+    async fn component_index(&self) -> Result<Vec<ComponentEntry>> {
+        Ok(self.components.entries())
+    }
+
+    /// Handles the custom `angular/selectorUsages` request, the usages of a component grouped by template file.
+    async fn selector_usages(&self, params: SelectorUsagesParams) -> Result<Option<SelectorUsages>> {
+        self.index_lazily(&params.selector).await;
+        let Some(component) = self.components.get(&params.selector).map(|component| component.clone()) else {
+            return Ok(None);
+        };
+        let locations = self.find_in_templates(&mut |root, rope| usages::element_usages(root, rope, &params.selector));
+        Ok(Some(usages::selector_usages(&component, locations)))
+    }
+
+    /// Logs `message` to the client, unless the configured level leaves it out.
+    async fn log(&self, level: LogLevel, message: String) {
+        let log_level = self.config.read().unwrap().log_level;
+        if level <= log_level {
+            self.client.log_message(level.message_type(), message).await;
+        }
+    }
+
+    /// Indexes the components of the `roots` folders in the background.
+    fn index_folders(&self, roots: Vec<String>) {
+        tokio::spawn(self.indexing(roots, true));
+    }
+
+    /// Indexing of the components of the `roots` folders, which stops early
+    /// once a full re-indexing supersedes it, and skips the folders removed meanwhile.
+    /// With `persistIndex`, the unmodified files of the saved index are taken from it
+    /// when `reuse_cache`, and the index gets saved once complete.
+    fn indexing(&self, roots: Vec<String>, reuse_cache: bool) -> impl Future<Output = ()> + Send + 'static {
+        let root_globs: Vec<(String, Vec<String>)> = roots.iter()
+            .map(|root| (root.clone(), self.component_globs(root)))
+            .collect();
+        let progress_supported = self.work_done_progress.load(Ordering::Relaxed);
+        let component_index = self.components.clone();
+        let client = self.client.clone();
+        let document_map = self.document_map.clone();
+        let ast_map = self.ast_map.clone();
+        let template_map = self.template_map.clone();
+        let index_generation = self.index_generation.clone();
+        let generation = index_generation.load(Ordering::SeqCst);
+        let workspace_roots = self.workspace_roots.clone();
+        let indexing_tasks = self.indexing_tasks.clone();
+        let prefixes = self.component_prefixes();
+        let max_file_size = self.max_file_size();
+        let persist_index = self.config.read().unwrap().persist_index;
+        let log_level = self.config.read().unwrap().log_level;
+        let workers = self.config.read().unwrap().indexing_workers
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get).min(MAX_INDEXING_WORKERS))
+            .max(1);
+        async move {
+            indexing_tasks.fetch_add(1, Ordering::SeqCst);
+            send_status(&client, true, &component_index).await;
+            let start = Instant::now();
+            let files: Vec<PathBuf> = root_globs.iter()
+                .flat_map(|(root, globs)| ComponentAnalyzer::workspace_files(root, globs))
+                .collect();
+            let progress = IndexingProgress::begin(&client, progress_supported, files.len()).await;
+            let files = Arc::new(files);
+            let cache: Arc<HashMap<PathBuf, CachedFile>> = Arc::new(match persist_index && reuse_cache {
+                true => roots.iter().flat_map(|root| index_cache::load(root)).collect(),
+                false => HashMap::new(),
+            });
+            let cache_hits = Arc::new(AtomicUsize::new(0));
+            // Modification time of each file when it was indexed, for saving the index
+            let indexed = Arc::new(Mutex::new(Vec::new()));
+            let next_file = Arc::new(AtomicUsize::new(0));
+            let (done_sender, mut done_receiver) = tokio::sync::mpsc::unbounded_channel();
+            // Each worker parses with its own analyzer, taking the next file
+            // left, until none is left or a newer indexing supersedes this one
+            for _ in 0..workers.min(files.len()) {
+                let files = files.clone();
+                let next_file = next_file.clone();
+                let done_sender = done_sender.clone();
+                let document_map = document_map.clone();
+                let component_index = component_index.clone();
+                let index_generation = index_generation.clone();
+                let workspace_roots = workspace_roots.clone();
+                let cache = cache.clone();
+                let cache_hits = cache_hits.clone();
+                let indexed = indexed.clone();
+                tokio::task::spawn_blocking(move || {
+                    let Some(mut analyzer) = ComponentAnalyzer::new() else {
+                        error!("Error building analyzer");
+                        return;
+                    };
+                    while index_generation.load(Ordering::SeqCst) == generation {
+                        let Some(path) = files.get(next_file.fetch_add(1, Ordering::SeqCst)) else {
+                            break;
+                        };
+                        // The folder of the file may have been removed since the scan started
+                        let in_workspace = || workspace_roots.read().unwrap().iter().any(|root| path.starts_with(root));
+                        if in_workspace() {
+                            let file_url = Url::from_file_path(path).ok();
+                            // An open document may have unsaved changes
+                            let cached = cache.get(path)
+                                .filter(|cached| cached.is_fresh())
+                                .filter(|_| file_url.as_ref().is_some_and(|file_url| !document_map.contains_key(file_url.as_str())));
+                            if let (Some(cached), Some(file_url)) = (cached, &file_url) {
+                                component_index.update_file(file_url, cached.components.clone(), cached.pipes.clone(), cached.modules.clone());
+                                cache_hits.fetch_add(1, Ordering::SeqCst);
+                                indexed.lock().unwrap().push((path.clone(), cached.modified));
+                            } else {
+                                // Files too large to analyze are left out, for when the maximum changes
+                                let modified = persist_index.then(|| fs::metadata(path).ok()).flatten()
+                                    .filter(|metadata| metadata.len() <= max_file_size)
+                                    .and_then(|metadata| metadata.modified().ok());
+                                if let Err(e) = analyzer.analyze_file(path, &document_map, &component_index, max_file_size) {
+                                    warn!("Error analyzing file {:?} {:?}", path, e);
+                                } else if let Some(modified) = modified {
+                                    indexed.lock().unwrap().push((path.clone(), modified));
+                                }
+                            }
+                            // Removed while analyzing, after the index dropped the files of the folder
+                            if !in_workspace() {
+                                if let Ok(file_url) = Url::from_file_path(path) {
+                                    component_index.remove_file(&file_url);
+                                }
+                            }
+                        }
+                        if done_sender.send(()).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            // Reporting ends with the last worker, along with the last sender
+            drop(done_sender);
+            let mut done = 0;
+            while done_receiver.recv().await.is_some() {
+                done += 1;
+                progress.report(done).await;
+            }
+            progress.end().await;
+            if LogLevel::Info <= log_level {
+                let message = format!("Indexed {} files of {:?} in {:?} with {} workers, {} from the saved index, {} components known",
+                    files.len(), roots, start.elapsed(), workers, cache_hits.load(Ordering::SeqCst), component_index.component_count());
+                client.log_message(LogLevel::Info.message_type(), message).await;
+            }
+            // A superseded indexing saving only part of the files would make the next start slower
+            if persist_index && index_generation.load(Ordering::SeqCst) == generation {
+                let indexed: Vec<(PathBuf, SystemTime)> = indexed.lock().unwrap().iter()
+                    .filter(|(path, _)| Url::from_file_path(path).is_ok_and(|file_url| !document_map.contains_key(file_url.as_str())))
+                    .cloned()
+                    .collect();
+                for root in roots.iter() {
+                    if let Err(e) = index_cache::save(root, &indexed, &component_index) {
+                        warn!("Error saving the index of {} {:?}", root, e);
+                    }
+                }
+            }
+            let still_indexing = indexing_tasks.fetch_sub(1, Ordering::SeqCst) > 1;
+            send_status(&client, still_indexing, &component_index).await;
+            publish_all_diagnostics(&client, &document_map, &ast_map, &template_map, &component_index, &prefixes).await;
+        }
+    }
+
+    fn component_prefixes(&self) -> Vec<String> {
+        self.config.read().unwrap().component_prefixes.clone()
+    }
+
+    fn max_file_size(&self) -> u64 {
+        self.config.read().unwrap().max_file_size_bytes
+    }
+
+    /// Patterns of the files to index components from in the `root` workspace folder:
+    /// those configured, else the source roots of its `angular.json`, else the default.
+    fn component_globs(&self, root: &str) -> Vec<String> {
+        if let Some(globs) = self.config.read().unwrap().component_globs.clone() {
+            return globs;
+        }
+        angular_json::component_globs(root).unwrap_or_else(|| typescript_globs(DEFAULT_SOURCE_ROOT))
+    }
+
+    /// Patterns of the template files in the `root` workspace folder, next to the components:
+    /// those configured for components with the template extensions instead, else the
+    /// source roots of its `angular.json`, else the default.
+    fn template_globs(&self, root: &str) -> Vec<String> {
+        let config = self.config.read().unwrap();
+        let extensions = &config.template_extensions;
+        if let Some(globs) = &config.component_globs {
+            let mut template_globs: Vec<String> = Vec::new();
+            for component_glob in globs {
+                let Some((stem, _)) = component_glob.rsplit_once('.')
+                    .filter(|(_, extension)| TYPESCRIPT_EXTENSIONS.contains(extension)) else {
+                    continue;
+                };
+                for template_glob in extensions.iter().map(|extension| format!("{}.{}", stem, extension)) {
+                    if !template_globs.contains(&template_glob) {
+                        template_globs.push(template_glob);
+                    }
+                }
+            }
+            return template_globs;
+        }
+        angular_json::template_globs(root, extensions).unwrap_or_else(|| template_globs_under(DEFAULT_SOURCE_ROOT, extensions))
+    }
+
+    /// Analyzes the workspace files mentioning `selector` that aren't indexed yet,
+    /// so that the component is found before the background indexing reaches it.
+    /// Analyzed files stay in the index, later lookups don't read them again.
+    async fn index_lazily(&self, selector: &str) {
+        // Custom elements always have a dash, unlike the standard ones
+        if !selector.contains('-') || self.components.contains_key(selector) || self.lazy_misses.contains(selector) {
+            return;
+        }
+        // Once indexed on startup, what the index lacks isn't in the workspace files
+        if self.config.read().unwrap().index_on_startup && self.indexing_tasks.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let roots = self.workspace_roots.read().unwrap().clone();
+        let globs: Vec<(String, Vec<String>)> = roots.into_iter()
+            .map(|root| { let globs = self.component_globs(&root); (root, globs) })
+            .collect();
+        let components = self.components.clone();
+        let document_map = self.document_map.clone();
+        let max_file_size = self.max_file_size();
+        let wanted = selector.to_string();
+        let scan = tokio::task::spawn_blocking(move || {
+            let Some(mut analyzer) = ComponentAnalyzer::new() else {
+                error!("Error building analyzer");
+                return;
+            };
+            for path in globs.iter().flat_map(|(root, globs)| ComponentAnalyzer::workspace_files(root, globs)) {
+                let indexed = Url::from_file_path(&path).is_ok_and(|url| components.contains_file(&url));
+                if indexed || !fs::read_to_string(&path).is_ok_and(|contents| contents.contains(&wanted)) {
+                    continue;
+                }
+                if let Err(e) = analyzer.analyze_file(&path, &document_map, &components, max_file_size) {
+                    warn!("Error analyzing file {:?} {:?}", path, e);
+                }
+                if components.contains_key(&wanted) {
+                    break;
+                }
+            }
+        });
+        if let Err(e) = scan.await {
+            error!("Error scanning for {}: {:?}", selector, e);
+        }
+        if !self.components.contains_key(selector) {
+            self.lazy_misses.insert(selector.to_string());
+        }
+    }
+
+    /// Stores the latest content of a document along with its HTML tree,
+    /// which the incremental parse keeps cheap enough to do on every change.
+    /// Returns how the parse went, none for a document that isn't a template.
+    fn update_document(&self, params: TextDocumentItem) -> Option<ParseSummary> {
+        let rope = params.rope;
         self.document_map.insert(params.uri.to_string(), rope.clone());
-        let tree = self.parse_html(&params.text);
+        self.versions.insert(params.uri.to_string(), params.version);
+        self.completion_cache.remove(params.uri.as_str());
+        if is_typescript(params.uri.as_str()) {
+            self.lazy_misses.clear();
+        }
+        if !self.config.read().unwrap().is_template(params.uri.as_str()) {
+            return None;
+        }
+        let start = Instant::now();
+        let tree = self.parse_html(&rope, params.old_tree.as_ref());
+        let time = start.elapsed();
+        // Tree-sitter reuses the nodes out of these ranges from the edited old tree
+        let changed_ranges = params.old_tree.as_ref()
+            .map(|old_tree| old_tree.changed_ranges(&tree).collect());
         self.ast_map.insert(params.uri.to_string(), tree);
+        Some(ParseSummary { time, length: rope.len_bytes(), changed_ranges })
+    }
+
+    /// Analyzes a document once it stopped changing for `ANALYSIS_DELAY`,
+    /// dropping the analysis when a newer change bumped the document generation.
+    fn analyze_later(&self, uri: Url) {
+        let generation = self.next_generation(&uri);
+        let generations = self.generations.clone();
+        let client = self.client.clone();
+        let components = self.components.clone();
+        let html_analyzer = self.html_analyzer.clone();
+        let document_map = self.document_map.clone();
+        let ast_map = self.ast_map.clone();
+        let template_map = self.template_map.clone();
+        let completion_cache = self.completion_cache.clone();
+        let prefixes = self.component_prefixes();
+        tokio::spawn(async move {
+            tokio::time::sleep(ANALYSIS_DELAY).await;
+            if generations.get(uri.as_str()).map(|current| *current) != Some(generation) {
+                return;
+            }
+            if is_typescript(uri.as_str()) {
+                // The inputs of a component may have changed for every template using it
+                update_inline_templates(&uri, &html_analyzer, &document_map, &template_map);
+                completion_cache.remove(uri.as_str());
+                index_document(&uri, &document_map, &components);
+                publish_all_diagnostics(&client, &document_map, &ast_map, &template_map, &components, &prefixes).await;
+            } else if let Some(diagnostics) = document_diagnostics(uri.as_str(), &document_map, &ast_map, &template_map, &components, &prefixes) {
+                client.publish_diagnostics(uri, diagnostics, None).await;
+            }
+        });
+    }
+
+    /// Analyzes a document right away, superseding any pending analysis.
+    async fn analyze_now(&self, uri: Url) {
+        self.next_generation(&uri);
+        if is_typescript(uri.as_str()) {
+            update_inline_templates(&uri, &self.html_analyzer, &self.document_map, &self.template_map);
+            self.completion_cache.remove(uri.as_str());
+            index_document(&uri, &self.document_map, &self.components);
+            publish_all_diagnostics(&self.client, &self.document_map, &self.ast_map, &self.template_map, &self.components, &self.component_prefixes()).await;
+        } else if let Some(diagnostics) = document_diagnostics(uri.as_str(), &self.document_map, &self.ast_map, &self.template_map, &self.components, &self.component_prefixes()) {
+            self.client.publish_diagnostics(uri, diagnostics, None).await;
+        }
+    }
+
+    /// Drops everything known about a document that is no longer open, pending analyses included.
+    fn forget_document(&self, uri: &Url) {
+        self.document_map.remove(uri.as_str());
+        self.ast_map.remove(uri.as_str());
+        self.template_map.remove(uri.as_str());
+        self.generations.remove(uri.as_str());
+        self.versions.remove(uri.as_str());
+        self.completion_cache.remove(uri.as_str());
+    }
+
+    /// Generations are unique across documents, so that a reopened document doesn't reuse one.
+    fn next_generation(&self, uri: &Url) -> usize {
+        let generation = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+        self.generations.insert(uri.to_string(), generation);
+        generation
+    }
+
+    fn parse_html(&self, rope: &Rope, old_tree: Option<&Tree>) -> Tree {
+        self.html_analyzer.lock().unwrap().parse(rope, old_tree)
+    }
+
+    /// Content of a document, as open in the editor or else on disk.
+    fn document_contents(&self, uri: &Url) -> Option<String> {
+        if let Some(rope) = self.document_map.get(uri.as_str()) {
+            return Some(rope.to_string());
+        }
+        fs::read_to_string(uri.to_file_path().ok()?).ok()
+    }
+
+    /// Runs `f` on the HTML tree under `position`: either the document itself,
+    /// or for TypeScript documents the inline template containing the position.
+    fn with_template<T>(&self, uri: &Url, position: Position, f: impl FnOnce(&Node, usize, &Rope) -> Option<T>) -> Option<T> {
+        let rope = self.document_map.get(&uri.to_string())?;
+        let offset = to_offset(&rope, position)?;
+        if let Some(templates) = self.template_map.get(&uri.to_string()) {
+            let template = templates.iter().find(|template| template.contains(offset))?;
+            return f(&template.tree.root_node(), offset - template.start, &template.rope);
+        }
+        if let Some(ast) = self.ast_map.get(&uri.to_string()) {
+            return f(&ast.root_node(), offset, &rope);
+        }
+        // The rope may be stored before its tree, or its parse may have failed
+        if !self.config.read().unwrap().is_template(uri.as_str()) {
+            return None;
+        }
+        debug!("Parsing {} again, its tree is missing", uri);
+        let tree = self.parse_html(&rope, None);
+        let result = f(&tree.root_node(), offset, &rope);
+        self.ast_map.entry(uri.to_string()).or_insert(tree);
+        result
+    }
+
+    /// Selector under `position`, either as a template tag or as the selector
+    /// string of a component declaration, along with the range of that text.
+    fn selector_at(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let tag = self.with_template(uri, position, |root, offset, rope| {
+            element_name_range(&find_node(root, offset, vec!["tag_name"])?, rope)
+        });
+        if let Some((tag_name, range)) = tag {
+            return Some((tag_name, self.to_document_range(uri, position, range)));
+        }
+        self.components.iter()
+            .find(|component| component.file_url == *uri && range_contains(&component.selector_range, position))
+            .map(|component| (component.selector.clone(), component.selector_range))
+    }
+
+    /// Component declaring the inline template at `position` of a TypeScript document.
+    fn inline_template_owner(&self, uri: &Url, position: Position) -> Option<Component> {
+        let offset = self.document_map.get(uri.as_str()).and_then(|rope| to_offset(&rope, position))?;
+        let templates = self.template_map.get(uri.as_str())?;
+        templates.iter()
+            .find(|template| template.contains(offset))
+            .map(|template| template.owner.clone())
+    }
+
+    /// Maps a range computed by `with_template` at `position` back to document coordinates.
+    fn to_document_range(&self, uri: &Url, position: Position, range: Range) -> Range {
+        let offset = self.document_map.get(&uri.to_string())
+            .and_then(|rope| to_offset(&rope, position));
+        let templates = self.template_map.get(&uri.to_string());
+        match (offset, templates) {
+            (Some(offset), Some(templates)) => templates.iter()
+                .find(|template| template.contains(offset))
+                .map_or(range, |template| template.to_host_range(range)),
+            _ => range,
+        }
+    }
+
+    /// Input or output at `position`, either bound in a template or declared
+    /// in a component file, along with the range of its name there.
+    fn property_target(&self, uri: &Url, position: Position) -> Option<(PropertyTarget, Range)> {
+        let bound = self.with_template(uri, position, |root, offset, rope| {
+            let attribute_name = find_node(root, offset, vec!["attribute_name"])?;
+            let target = property_rename::template_target(&attribute_name, rope, &self.components)?;
+            Some((target, property_rename::name_range(&attribute_name, rope)?))
+        });
+        if let Some((target, range)) = bound {
+            return Some((target, self.to_document_range(uri, position, range)));
+        }
+        property_rename::declaration_target(uri, position, &self.components)
+            .map(|target| {
+                let range = property_rename::declaration_range(&target);
+                (target, range)
+            })
+    }
+
+    fn rename_property(&self, target: &PropertyTarget, new_name: &str) -> Result<Option<WorkspaceEdit>> {
+        if !property_rename::is_valid_property_name(new_name) {
+            return Err(Error::invalid_params(format!("'{}' is not a valid property name", new_name)));
+        }
+        let Some(rename) = property_rename::rename(target, new_name) else {
+            return Err(Error::invalid_params(format!("Rename the model '{}' rather than its output", target.property.name)));
+        };
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        changes.entry(target.component.file_url.clone()).or_default().extend(rename.declaration_edits);
+        for binding in rename.bindings {
+            let mut find = |root: &Node, rope: &Rope| property_rename::binding_references(root, rope, &self.components, &target.component.selector, &binding);
+            for location in self.find_in_templates(&mut find) {
+                changes.entry(location.uri)
+                    .or_default()
+                    .push(TextEdit::new(location.range, binding.new_name.clone()));
+            }
+        }
+        for edits in changes.values_mut() {
+            edits.sort_by_key(|edit| edit.range.start);
+            edits.dedup_by_key(|edit| edit.range);
+        }
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    /// Resolves an attribute to the component property it binds to, or to
+    /// the attribute directive it applies.
+    fn attribute_definition(&self, attribute_name: &Node, rope: &Rope) -> Option<GotoDefinitionResponse> {
+        let attribute = node_text(attribute_name, rope)?;
+        let (kind, name) = parse_binding(&attribute).unwrap_or((BindingKind::Property, &attribute));
+        let tag_name = attribute_name.parent()?.parent()?.named_child(0)
+            .and_then(|tag_name| node_text(&tag_name, rope));
+        let property_location = tag_name
+            .and_then(|tag_name| self.components.get(&tag_name))
+            .and_then(|component| {
+                let properties = match kind {
+                    BindingKind::Event => &component.outputs,
+                    BindingKind::Property | BindingKind::TwoWay => &component.inputs,
+                };
+                properties.iter()
+                    .find(|property| property.binding_name() == name)
+                    .map(|property| Location::new(component.file_url.clone(), property.range))
+            });
+        property_location
+            .or_else(|| self.components.iter()
+                .find(|directive| attribute_selector(&directive.selector) == Some(name))
+                .map(|directive| Location::new(directive.file_url.clone(), directive.class_name_range)))
+            .map(GotoDefinitionResponse::Scalar)
     }
 
-    fn parse_html(&self, content: &String) -> Tree {
-        let mut html_analyzer = HtmlAnalyzer::new();
-        return html_analyzer.parser.parse(content, None).unwrap();
+    /// Locations of every `selector` tag in open documents, their inline
+    /// templates, and the workspace HTML files that are not open.
+    fn find_references(&self, selector: &str) -> Vec<Location> {
+        self.find_in_templates(&mut |root, rope| find_tag_references(root, rope, selector))
+    }
+
+    /// Locations of the ranges that `find` finds in every template: open documents,
+    /// their inline templates, and the workspace HTML files that are not open.
+    fn find_in_templates(&self, find: &mut dyn FnMut(&Node, &Rope) -> Vec<Range>) -> Vec<Location> {
+        let mut locations = Vec::new();
+        for document in self.document_map.iter() {
+            let Ok(uri) = Url::parse(document.key()) else {
+                continue;
+            };
+            if let Some(templates) = self.template_map.get(document.key()) {
+                for template in templates.iter() {
+                    locations.extend(find(&template.tree.root_node(), &template.rope)
+                        .into_iter()
+                        .map(|range| Location::new(uri.clone(), template.to_host_range(range))));
+                }
+            } else if let Some(ast) = self.ast_map.get(document.key()) {
+                locations.extend(find(&ast.root_node(), document.value())
+                    .into_iter()
+                    .map(|range| Location::new(uri.clone(), range)));
+            }
+        }
+
+        for (uri, path) in self.template_files() {
+            if self.document_map.contains_key(&uri.to_string()) {
+                continue;
+            }
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    let rope = Rope::from_str(&contents);
+                    let tree = self.parse_html(&rope, None);
+                    locations.extend(find(&tree.root_node(), &rope)
+                        .into_iter()
+                        .map(|range| Location::new(uri.clone(), range)));
+                }
+                Err(e) => warn!("Error reading file {:?} {:?}", path, e),
+            }
+        }
+        locations
+    }
+
+    /// Patterns of the template files of each workspace folder.
+    fn template_root_globs(&self) -> Vec<(String, Vec<String>)> {
+        let roots = self.workspace_roots.read().unwrap().clone();
+        roots.into_iter()
+            .map(|root| {
+                let globs = self.template_globs(&root);
+                (root, globs)
+            })
+            .collect()
+    }
+
+    /// Template files of the workspace folders, along with their URL.
+    fn template_files(&self) -> Vec<(Url, PathBuf)> {
+        files_matching(&self.template_root_globs())
+    }
+
+    /// Number of elements of each name in every template, open documents as edited.
+    /// Template files are read once, then again after a reindexing, as they get saved,
+    /// created or deleted.
+    async fn usage_counts(&self) -> HashMap<String, usize> {
+        if !self.template_files_counted.swap(true, Ordering::SeqCst) {
+            let root_globs = self.template_root_globs();
+            // Reading and parsing every template file would hold up the other requests
+            let counting = tokio::task::spawn_blocking(move || {
+                let mut analyzer = HtmlAnalyzer::new();
+                files_matching(&root_globs).into_iter()
+                    .filter_map(|(uri, path)| {
+                        let rope = Rope::from_str(&fs::read_to_string(&path).ok()?);
+                        let mut counts = HashMap::new();
+                        usages::element_counts(&analyzer.parse(&rope, None).root_node(), &rope, &mut counts);
+                        Some((uri, counts))
+                    })
+                    .collect::<Vec<_>>()
+            });
+            match counting.await {
+                Ok(files) => {
+                    self.template_file_usages.clear();
+                    for (uri, counts) in files {
+                        self.template_file_usages.insert(uri, counts);
+                    }
+                }
+                Err(e) => {
+                    error!("Error counting the usages in template files {:?}", e);
+                    self.template_files_counted.store(false, Ordering::SeqCst);
+                }
+            }
+        }
+        let mut totals = HashMap::new();
+        for file in self.template_file_usages.iter().filter(|file| !self.document_map.contains_key(file.key().as_str())) {
+            for (name, count) in file.value() {
+                *totals.entry(name.clone()).or_insert(0) += count;
+            }
+        }
+        for document in self.document_map.iter() {
+            if let Some(templates) = self.template_map.get(document.key()) {
+                for template in templates.iter() {
+                    usages::element_counts(&template.tree.root_node(), &template.rope, &mut totals);
+                }
+            } else if let Some(ast) = self.ast_map.get(document.key()) {
+                usages::element_counts(&ast.root_node(), document.value(), &mut totals);
+            }
+        }
+        totals
     }
 }
 
@@ -395,12 +2816,711 @@ async fn main() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::build(|client| Backend {
-        client,
-        components: Arc::new(DashMap::new()),
-        ast_map: DashMap::new(),
-        document_map: DashMap::new(),
-    })
+    let (service, socket) = LspService::build(Backend::new)
+    .custom_method("angular/componentIndex", Backend::component_index)
+    .custom_method("angular/selectorUsages", Backend::selector_usages)
+    .custom_method("textDocument/inlayHint", Backend::inlay_hint)
+    .custom_method(pull_diagnostics::METHOD, Backend::document_diagnostic)
+    .custom_method(type_hierarchy::PREPARE_METHOD, Backend::prepare_type_hierarchy)
+    .custom_method(type_hierarchy::SUPERTYPES_METHOD, Backend::type_hierarchy_supertypes)
+    .custom_method(type_hierarchy::SUBTYPES_METHOD, Backend::type_hierarchy_subtypes)
     .finish();
     Server::new(stdin, stdout, socket).serve(service).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{file_url, folder, index, open, open_url, server, template};
+
+    const CARD: &str = r#"
+@Component({ selector: 'app-card', template: '' })
+export class CardComponent {
+  @Input() title: string;
+}"#;
+
+    fn diagnostics_of(backend: &Backend, uri: &Url) -> Vec<Diagnostic> {
+        document_diagnostics(uri.as_str(), &backend.document_map, &backend.ast_map, &backend.template_map,
+            &backend.components, &backend.component_prefixes()).unwrap_or_default()
+    }
+
+    #[test]
+    fn indexing_skips_broken_files() {
+        let root = folder(&[
+            ("src/app/card.component.ts", CARD),
+            ("src/app/broken.component.ts", "@Component({ selector: 'app-broken', template: '' \nexport class {"),
+        ]);
+        fs::write(root.join("src/app/binary.ts"), [0xff, 0xfe, 0x00]).unwrap();
+        let index = ComponentIndex::default();
+        let mut analyzer = ComponentAnalyzer::new().unwrap();
+        let files = ComponentAnalyzer::workspace_files(root.to_str().unwrap(), &typescript_globs(DEFAULT_SOURCE_ROOT));
+        let failed: Vec<_> = files.iter()
+            .filter(|path| analyzer.analyze_file(path, &DashMap::new(), &index, u64::MAX).is_err())
+            .collect();
+        assert_eq!(files.len(), 3);
+        assert_eq!(failed, vec![&root.join("src/app/binary.ts")]);
+        assert!(index.contains_key("app-card"));
+        assert!(!index.contains_key("app-broken"));
+    }
+
+    #[test]
+    fn files_over_the_size_limit_are_skipped_unless_open() {
+        let large = format!("{}\n// {}", CARD.replace("app-card", "app-large"), "x".repeat(1000));
+        let root = folder(&[("src/app/card.component.ts", CARD), ("src/app/large.component.ts", &large)]);
+        let index = ComponentIndex::default();
+        let mut analyzer = ComponentAnalyzer::new().unwrap();
+        let limit = CARD.len() as u64;
+        for path in ComponentAnalyzer::workspace_files(root.to_str().unwrap(), &typescript_globs(DEFAULT_SOURCE_ROOT)) {
+            analyzer.analyze_file(&path, &DashMap::new(), &index, limit).unwrap();
+        }
+        assert!(index.contains_key("app-card"));
+        assert!(!index.contains_key("app-large"));
+
+        // An open buffer is already in memory
+        let path = root.join("src/app/large.component.ts");
+        let document_map = DashMap::new();
+        document_map.insert(Url::from_file_path(&path).unwrap().to_string(), Rope::from_str(&large));
+        analyzer.analyze_file(&path, &document_map, &index, limit).unwrap();
+        assert!(index.contains_key("app-large"));
+    }
+
+    #[test]
+    fn tsx_and_es_module_sources_are_indexed() {
+        let root = folder(&[
+            ("src/app/badge.component.tsx", r#"
+@Component({ selector: 'app-badge', template: '' })
+export class BadgeComponent {
+  @Input() label: string;
+  render() { return <span className="badge">{this.label}</span>; }
+}"#),
+            ("src/app/card.component.mts", CARD),
+        ]);
+        let index = ComponentIndex::default();
+        let mut analyzer = ComponentAnalyzer::new().unwrap();
+        for path in ComponentAnalyzer::workspace_files(root.to_str().unwrap(), &typescript_globs(DEFAULT_SOURCE_ROOT)) {
+            analyzer.analyze_file(&path, &DashMap::new(), &index, u64::MAX).unwrap();
+        }
+        let badge = index.get("app-badge").unwrap();
+        assert_eq!(badge.class_name, "BadgeComponent");
+        assert_eq!(badge.inputs.iter().map(|input| input.name.as_str()).collect::<Vec<_>>(), ["label"]);
+        assert!(index.contains_key("app-card"));
+    }
+
+    #[test]
+    fn deepest_nodes_contain_the_offset_their_ends_included() {
+        let (_, tree) = template("<ul><li>One</li></ul>");
+        let root = tree.root_node();
+        let found = |offset, types: &[&str]| find_deepest(root, offset, types).map(|node| (node.kind(), node.byte_range()));
+        // Nested elements
+        assert_eq!(found(9, &["element"]), Some(("element", 4..16)));
+        assert_eq!(found(2, &["element"]), Some(("element", 0..21)));
+        // At a boundary the node ending there comes first, the tokens ending there hiding nothing
+        assert_eq!(found(4, &["start_tag"]), Some(("start_tag", 0..4)));
+        assert_eq!(found(8, &["element", "start_tag"]), Some(("start_tag", 4..8)));
+        assert_eq!(found(8, &["text"]), Some(("text", 8..11)));
+        assert_eq!(found(21, &["element"]), Some(("element", 0..21)));
+        // No node of the types
+        assert_eq!(found(9, &["attribute"]), None);
+        assert_eq!(found(22, &["element"]), None);
+    }
+
+    #[tokio::test]
+    async fn closed_documents_are_forgotten() {
+        let backend = &server();
+        let uri = open(backend, "src/app/list.component.html", "<ul><li></li></ul>").await;
+        assert!(backend.ast_map.contains_key(uri.as_str()));
+        backend.did_close(DidCloseTextDocumentParams { text_document: TextDocumentIdentifier::new(uri.clone()) }).await;
+        assert!(!backend.document_map.contains_key(uri.as_str()));
+        assert!(!backend.ast_map.contains_key(uri.as_str()));
+    }
+
+    #[tokio::test]
+    async fn deleted_files_leave_the_index() {
+        let backend = &server();
+        let uri = open(backend, "src/app/card.component.ts", CARD).await;
+        assert!(backend.components.contains_key("app-card"));
+        backend.did_change_watched_files(DidChangeWatchedFilesParams {
+            changes: vec![FileEvent::new(uri.clone(), FileChangeType::DELETED)],
+        }).await;
+        assert!(!backend.components.contains_key("app-card"));
+        assert!(!backend.document_map.contains_key(uri.as_str()));
+        assert!(!backend.template_map.contains_key(uri.as_str()));
+    }
+
+    #[tokio::test]
+    async fn lazy_indexing_remembers_misses_until_files_change() {
+        let backend = &server();
+        backend.config.write().unwrap().index_on_startup = false;
+        let root = folder(&[("src/app/card.component.ts", CARD)]);
+        backend.workspace_roots.write().unwrap().push(root.to_str().unwrap().to_string());
+        backend.index_lazily("app-missing").await;
+        assert!(backend.lazy_misses.contains("app-missing"));
+        backend.index_lazily("app-card").await;
+        assert!(backend.components.contains_key("app-card"));
+        assert!(backend.lazy_misses.contains("app-missing"));
+
+        // Only new or changed files may declare it
+        let path = root.join("src/app/missing.component.ts");
+        fs::write(&path, CARD.replace("app-card", "app-missing")).unwrap();
+        fs::write(root.join("src/app/other.component.ts"), CARD.replace("app-card", "app-other")).unwrap();
+        backend.did_change_watched_files(DidChangeWatchedFilesParams {
+            changes: vec![FileEvent::new(Url::from_file_path(&path).unwrap(), FileChangeType::CREATED)],
+        }).await;
+        assert!(backend.lazy_misses.is_empty());
+        backend.index_lazily("app-other").await;
+        assert!(backend.components.contains_key("app-other"));
+    }
+
+    #[tokio::test]
+    async fn lazy_indexing_stops_once_indexed_on_startup() {
+        let backend = &server();
+        let root = folder(&[("src/app/card.component.ts", CARD)]);
+        backend.workspace_roots.write().unwrap().push(root.to_str().unwrap().to_string());
+        // Indexed on startup, with no indexing running
+        backend.index_lazily("app-card").await;
+        assert!(!backend.components.contains_key("app-card"));
+        assert!(backend.lazy_misses.is_empty());
+        backend.indexing_tasks.fetch_add(1, Ordering::SeqCst);
+        backend.index_lazily("app-card").await;
+        assert!(backend.components.contains_key("app-card"));
+    }
+
+    #[tokio::test]
+    async fn reindexing_during_a_scan_declares_each_component_once() {
+        let backend = &server();
+        let files: Vec<(String, String)> = (0..100)
+            .map(|n| (format!("src/app/item-{}.component.ts", n), CARD.replace("app-card", &format!("app-item-{}", n))))
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(path, contents)| (path.as_str(), contents.as_str())).collect();
+        let root = folder(&files).to_str().unwrap().to_string();
+        backend.workspace_roots.write().unwrap().push(root.clone());
+        backend.index_folders(vec![root]);
+        tokio::task::yield_now().await;
+        backend.execute_command(ExecuteCommandParams {
+            command: String::from(REINDEX_COMMAND),
+            arguments: Vec::new(),
+            work_done_progress_params: Default::default(),
+        }).await.unwrap();
+        while backend.indexing_tasks.load(Ordering::SeqCst) > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(backend.components.component_count(), 100);
+        assert!((0..100).all(|n| backend.components.declarations(&format!("app-item-{}", n)).len() == 1));
+    }
+
+    #[tokio::test]
+    async fn folders_wait_for_demand_when_not_indexed_on_startup() {
+        let backend = &server();
+        let root = folder(&[("src/app/card.component.ts", CARD)]);
+        backend.initialize(InitializeParams {
+            initialization_options: Some(serde_json::json!({ "indexOnStartup": false })),
+            workspace_folders: Some(vec![WorkspaceFolder {
+                uri: Url::from_directory_path(&root).unwrap(),
+                name: String::from("workspace"),
+            }]),
+            ..InitializeParams::default()
+        }).await.unwrap();
+        backend.initialized(InitializedParams {}).await;
+        // Time enough for indexing on startup to go through
+        tokio::time::sleep(ANALYSIS_DELAY * 2).await;
+        assert!(backend.components.iter().next().is_none());
+        backend.index_lazily("app-card").await;
+        assert!(backend.components.contains_key("app-card"));
+    }
+
+    #[tokio::test]
+    async fn inline_template_diagnostics_point_into_the_component_file() {
+        let backend = &server();
+        let uri = open(backend, "src/app/list.component.ts", r#"@Component({
+  selector: 'app-list',
+  template: `<app-top></app-top><ul>
+    <li><app-unknown></app-unknown></li>
+  </ul>`,
+})
+export class ListComponent {}"#).await;
+        let ranges: Vec<Range> = diagnostics_of(backend, &uri).into_iter()
+            .map(|diagnostic| diagnostic.range)
+            .collect();
+        // Only the first line of the template starts after code of the host document
+        assert_eq!(ranges, vec![
+            Range::new(Position::new(2, 14), Position::new(2, 21)),
+            Range::new(Position::new(3, 9), Position::new(3, 20)),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn completions_are_reused_until_the_document_changes() {
+        let backend = &server();
+        open(backend, "src/app/card.component.ts", CARD).await;
+        let uri = open(backend, "src/app/list.component.html", "<ul><</ul>").await;
+        let labels = completion_labels(backend, &uri, 0, 5).await;
+        assert!(labels.iter().any(|label| label == "app-card"));
+        assert_eq!(completion_labels(backend, &uri, 0, 5).await, labels);
+
+        // A request again at the same version and position answers from the cache
+        backend.completion_cache.get_mut(uri.as_str()).unwrap().1 = vec![CompletionItem::new_simple(String::from("cached"), String::new())];
+        assert_eq!(completion_labels(backend, &uri, 0, 5).await, ["cached"]);
+        assert_ne!(completion_labels(backend, &uri, 0, 4).await, ["cached"]);
+        backend.did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(0, 5), Position::new(0, 5))),
+                range_length: None,
+                text: String::new(),
+            }],
+        }).await;
+        assert_eq!(completion_labels(backend, &uri, 0, 5).await, labels);
+    }
+
+    #[tokio::test]
+    async fn unused_components_are_those_of_no_template() {
+        let backend = &server();
+        open(backend, "src/app/card.component.ts", CARD).await;
+        let badge_uri = open(backend, "src/app/badge.component.ts",
+            "@Component({ selector: 'app-badge', template: '' })\nexport class BadgeComponent {}").await;
+        open(backend, "src/app/list.component.html", "<ul><li><app-card></app-card></li></ul>").await;
+        let unused = backend.execute_command(ExecuteCommandParams {
+            command: String::from(FIND_UNUSED_COMPONENTS_COMMAND),
+            arguments: Vec::new(),
+            work_done_progress_params: Default::default(),
+        }).await.unwrap();
+        let class_name_range = backend.components.get("app-badge").unwrap().class_name_range;
+        assert_eq!(unused, Some(json!([{
+            "selector": "app-badge",
+            "className": "BadgeComponent",
+            "location": Location::new(badge_uri, class_name_range),
+        }])));
+    }
+
+    #[tokio::test]
+    async fn saved_content_wins_over_the_buffer() {
+        let backend = &server();
+        let uri = open(backend, "src/app/list.component.html", "<ul></ul>").await;
+        assert!(diagnostics_of(backend, &uri).is_empty());
+        backend.did_save(DidSaveTextDocumentParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            text: Some(String::from("<ul><app-unknown></app-unknown></ul>")),
+        }).await;
+        let diagnostics: Vec<(Range, String)> = diagnostics_of(backend, &uri).into_iter()
+            .map(|diagnostic| (diagnostic.range, diagnostic.message))
+            .collect();
+        assert_eq!(diagnostics, [
+            (Range::new(Position::new(0, 5), Position::new(0, 16)), String::from("Unknown component 'app-unknown'")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn usage_lenses_follow_the_template_files() {
+        let backend = &server();
+        let root = folder(&[("src/app/list.component.html", "<ul><li><app-card></app-card></li></ul>")]);
+        backend.workspace_roots.write().unwrap().push(root.to_str().unwrap().to_string());
+        open(backend, "src/app/card.component.ts", CARD).await;
+        let uri = open(backend, "src/app/page.component.html", "<app-card></app-card>").await;
+        let usage_titles = || async {
+            let lenses = backend.code_lens(CodeLensParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            }).await.unwrap().unwrap_or_default();
+            lenses.into_iter()
+                .filter_map(|lens| lens.command)
+                .filter(|command| command.command == code_lens::SHOW_REFERENCES_COMMAND)
+                .map(|command| command.title)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(usage_titles().await, ["2 usages"]);
+
+        let watched = |path: &str, typ| {
+            let uri = Url::from_file_path(root.join(path)).unwrap();
+            DidChangeWatchedFilesParams { changes: vec![FileEvent::new(uri, typ)] }
+        };
+        fs::remove_file(root.join("src/app/list.component.html")).unwrap();
+        backend.did_change_watched_files(watched("src/app/list.component.html", FileChangeType::DELETED)).await;
+        assert_eq!(usage_titles().await, ["1 usage"]);
+        fs::write(root.join("src/app/grid.component.html"), "<app-card></app-card><app-card></app-card>").unwrap();
+        backend.did_change_watched_files(watched("src/app/grid.component.html", FileChangeType::CREATED)).await;
+        assert_eq!(usage_titles().await, ["3 usages"]);
+        fs::write(root.join("src/app/grid.component.html"), "<app-card></app-card>").unwrap();
+        backend.did_change_watched_files(watched("src/app/grid.component.html", FileChangeType::CHANGED)).await;
+        assert_eq!(usage_titles().await, ["2 usages"]);
+        fs::write(root.join("src/app/grid.component.html"), "<app-card></app-card><app-card></app-card>").unwrap();
+        backend.did_change_watched_files(watched("angular.json", FileChangeType::CHANGED)).await;
+        open(backend, "src/app/card.component.ts", CARD).await;
+        assert_eq!(usage_titles().await, ["3 usages"]);
+
+        // The lens command answers the references of the element
+        let references = backend.execute_command(ExecuteCommandParams {
+            command: String::from(code_lens::SHOW_REFERENCES_COMMAND),
+            arguments: vec![json!(uri), json!(Position::new(0, 1))],
+            work_done_progress_params: Default::default(),
+        }).await.unwrap().unwrap();
+        let references: Vec<Location> = serde_json::from_value(references).unwrap();
+        let mut files: Vec<&str> = references.iter()
+            .map(|location| location.uri.path().rsplit('/').next().unwrap())
+            .collect();
+        files.dedup();
+        assert_eq!(files, ["page.component.html", "grid.component.html"]);
+    }
+
+    #[tokio::test]
+    async fn open_component_files_are_indexed_from_their_buffer() {
+        let backend = &server();
+        let root = folder(&[("src/app/card.component.ts", CARD)]);
+        let path = root.join("src/app/card.component.ts");
+        let uri = Url::from_file_path(&path).unwrap();
+        open_url(backend, &uri, CARD).await;
+        backend.did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(3, 11), Position::new(3, 16))),
+                range_length: None,
+                text: String::from("heading"),
+            }],
+        }).await;
+        tokio::time::sleep(ANALYSIS_DELAY * 2).await;
+        let inputs = |backend: &Backend| -> Vec<String> {
+            backend.components.get("app-card").unwrap().inputs.iter().map(|input| input.binding_name().to_owned()).collect()
+        };
+        assert_eq!(inputs(backend), vec!["heading"]);
+
+        // Re-indexing the file keeps to the unsaved content
+        let mut analyzer = ComponentAnalyzer::new().unwrap();
+        analyzer.analyze_file(&path, &backend.document_map, &backend.components, u64::MAX).unwrap();
+        assert_eq!(inputs(backend), vec!["heading"]);
+    }
+
+    #[test]
+    fn components_need_not_be_exported() {
+        let index = index(&[("src/app/card.component.spec.ts", r#"
+@Component({ selector: 'app-host', template: '<app-card></app-card>' })
+class HostComponent {}
+
+/** Wraps the card under test. */
+@Component({ selector: 'app-wrapper', template: '' })
+class WrapperComponent {}
+
+@Component({ selector: 'app-exported', template: '' })
+export class ExportedComponent {}"#)]);
+        let host = index.get("app-host").unwrap();
+        assert_eq!(host.class_name, "HostComponent");
+        assert_eq!(host.declaration_range.start, Position::new(1, 0));
+        assert_eq!(index.get("app-wrapper").unwrap().documentation.as_deref(), Some("Wraps the card under test."));
+        assert_eq!(index.declarations("app-exported").len(), 1);
+        assert_eq!(index.component_count(), 3);
+    }
+
+    #[test]
+    fn analyzers_share_the_compiled_queries() {
+        let first = ComponentAnalyzer::new().unwrap();
+        let second = ComponentAnalyzer::new().unwrap();
+        assert!(std::ptr::eq(first.queries, second.queries));
+    }
+
+    #[tokio::test]
+    async fn changes_without_content_leave_the_document_alone() {
+        let backend = &server();
+        let uri = open(backend, "src/app/list.component.html", "<ul></ul>").await;
+        let empty_insertion = TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(0, 4), Position::new(0, 4))),
+            range_length: None,
+            text: String::new(),
+        };
+        // No analysis gets scheduled either
+        let generation = backend.generations.get(uri.as_str()).map(|generation| *generation);
+        for content_changes in [Vec::new(), vec![empty_insertion]] {
+            backend.did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+                content_changes,
+            }).await;
+            assert_eq!(backend.document_map.get(uri.as_str()).unwrap().to_string(), "<ul></ul>");
+            assert!(backend.ast_map.contains_key(uri.as_str()));
+            assert_eq!(backend.generations.get(uri.as_str()).map(|generation| *generation), generation);
+        }
+    }
+
+    const NUMBERED: &str = r#"
+@Component({ selector: ' my-comp-2 ', template: '' })
+export class MyComp2Component {
+  @Input() level: number;
+}"#;
+
+    const CONTAINERS: &str = r#"<ng-container *ngIf="shown">
+  <ng-container [ngTemplateOutlet]="row">
+    <ng-container>
+      <my-comp-2 ></my-comp-2>
+      <My-Comp-2></My-Comp-2>
+      <my-
+    </ng-container>
+  </ng-container>
+</ng-container>"#;
+
+    fn at(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri.clone()), Position::new(line, character))
+    }
+
+    async fn definition(backend: &Backend, uri: &Url, line: u32, character: u32) -> Option<GotoDefinitionResponse> {
+        backend.goto_definition(GotoDefinitionParams {
+            text_document_position_params: at(uri, line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        }).await.unwrap()
+    }
+
+    async fn completion_labels(backend: &Backend, uri: &Url, line: u32, character: u32) -> Vec<String> {
+        let response = backend.completion(CompletionParams {
+            text_document_position: at(uri, line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        }).await.unwrap();
+        let items = match response {
+            Some(CompletionResponse::Array(items)) => items,
+            Some(CompletionResponse::List(list)) => list.items,
+            None => Vec::new(),
+        };
+        items.into_iter().map(|item| item.label).collect()
+    }
+
+    #[tokio::test]
+    async fn templates_missing_their_tree_are_parsed_again() {
+        let backend = &server();
+        let component_uri = open(backend, "src/app/card.component.ts", CARD).await;
+        let uri = file_url("src/app/list.component.html");
+        backend.document_map.insert(uri.to_string(), Rope::from_str("<ul><app-card></app-card><</ul>"));
+        assert!(completion_labels(backend, &uri, 0, 26).await.contains(&String::from("app-card")));
+        assert!(backend.ast_map.contains_key(uri.as_str()));
+
+        backend.ast_map.remove(uri.as_str());
+        let class_name_range = backend.components.get("app-card").unwrap().class_name_range;
+        assert_eq!(definition(backend, &uri, 0, 6).await, Some(GotoDefinitionResponse::Scalar(Location::new(component_uri, class_name_range))));
+        // Other documents have no tree to make up
+        let readme = file_url("README.md");
+        backend.document_map.insert(readme.to_string(), Rope::from_str("<app-card>"));
+        assert!(completion_labels(backend, &readme, 0, 1).await.is_empty());
+        assert!(!backend.ast_map.contains_key(readme.as_str()));
+    }
+
+    #[tokio::test]
+    async fn pipes_resolve_to_their_class() {
+        let backend = &server();
+        let pipe_uri = open(backend, "src/app/money.pipe.ts", "@Pipe({ name: 'money' })\nexport class MoneyPipe {}").await;
+        let uri = open(backend, "src/app/price.component.html", "<p>{{ x | money }}</p>").await;
+        let class_name_range = backend.components.pipe("money").unwrap().class_name_range;
+        assert_eq!(class_name_range, Range::new(Position::new(1, 13), Position::new(1, 22)));
+        assert_eq!(definition(backend, &uri, 0, 12).await, Some(GotoDefinitionResponse::Scalar(Location::new(pipe_uri, class_name_range))));
+    }
+
+    #[tokio::test]
+    async fn components_resolve_inside_nested_containers() {
+        let backend = &server();
+        let component_uri = open(backend, "src/app/my-comp-2.component.ts", NUMBERED).await;
+        let uri = open(backend, "src/app/rows.component.html", CONTAINERS).await;
+        let expected = Some(GotoDefinitionResponse::Scalar(Location::new(component_uri,
+            backend.components.get("my-comp-2").unwrap().class_name_range)));
+        assert_eq!(definition(backend, &uri, 3, 9).await, expected);
+        assert_eq!(definition(backend, &uri, 3, 27).await, expected);
+        // Element selectors are case-sensitive
+        assert_eq!(definition(backend, &uri, 4, 9).await, None);
+
+        assert!(completion_labels(backend, &uri, 3, 17).await.contains(&String::from("level")));
+        assert!(completion_labels(backend, &uri, 5, 10).await.contains(&String::from("my-comp-2")));
+    }
+
+    #[test]
+    fn positions_count_utf16_code_units() {
+        let rope = Rope::from_str("<p>😀 é</p>\n<i>é😀</i>");
+        // The emoji takes two code units and four bytes, `é` one code unit and two bytes
+        assert_eq!(to_offset(&rope, Position::new(0, 5)), Some(7));
+        assert_eq!(to_offset(&rope, Position::new(0, 7)), Some(10));
+        assert_eq!(to_offset(&rope, Position::new(1, 6)), Some(24));
+        // Past the end of a line is its end, before the line break
+        assert_eq!(to_offset(&rope, Position::new(0, 99)), Some(14));
+        assert_eq!(to_offset(&rope, Position::new(1, 99)), Some(rope.len_bytes()));
+        for (byte, position) in [(7, Position::new(0, 5)), (10, Position::new(0, 7)), (24, Position::new(1, 6))] {
+            assert_eq!(to_position(&rope, byte, byte_to_point(&rope, byte)), position);
+        }
+    }
+
+    #[test]
+    fn ranges_after_multibyte_characters_count_utf16_code_units() {
+        let (rope, tree) = template("<p>😀</p><app-x></app-x>");
+        let diagnostics = diagnostics::diagnostics(&tree.root_node(), &rope, &ComponentIndex::default(), None, &[]);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(0, 10), Position::new(0, 15)));
+    }
+
+    #[tokio::test]
+    async fn documents_of_other_extensions_are_not_templates() {
+        let backend = &server();
+        let uri = open(backend, "docs/README.md", "# Cards\n\n<app-card></app-card>").await;
+        assert!(backend.document_map.contains_key(uri.as_str()));
+        assert!(!backend.ast_map.contains_key(uri.as_str()));
+        assert!(diagnostics_of(backend, &uri).is_empty());
+    }
+
+    #[tokio::test]
+    async fn component_files_keep_their_inline_templates_apart() {
+        let backend = &server();
+        let text = "@Component({\n  selector: 'app-list',\n  template: `<ul><li></li></ul>`,\n})\nexport class ListComponent {}";
+        let uri = open(backend, "src/app/list.component.ts", text).await;
+        assert!(!backend.ast_map.contains_key(uri.as_str()));
+        let templates = backend.template_map.get(uri.as_str()).unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].rope.to_string(), "<ul><li></li></ul>");
+        assert_eq!(templates[0].start, text.find("<ul>").unwrap());
+        assert_eq!(templates[0].owner.class_name, "ListComponent");
+        assert_eq!(backend.components.get("app-list").unwrap().file_url, uri);
+    }
+
+    #[test]
+    fn selectors_may_be_template_literals_or_constants() {
+        let index = index(&[("src/app/selectors.component.ts", r#"
+const BADGE_SELECTOR = `app-badge`;
+export const ALERT_SELECTOR = 'app-alert';
+const prefix = 'app';
+
+@Component({ selector: `app-card`, template: '' })
+export class CardComponent {}
+
+@Component({ selector: BADGE_SELECTOR, template: '' })
+export class BadgeComponent {}
+
+@Component({ selector: ALERT_SELECTOR, template: '' })
+export class AlertComponent {}
+
+@Component({ selector: `${prefix}-dynamic`, template: '' })
+export class DynamicComponent {}
+
+@Component({ selector: UNDECLARED, template: '' })
+export class UndeclaredComponent {}"#)]);
+        let card = index.get("app-card").unwrap();
+        assert_eq!(card.selector_range, Range::new(Position::new(5, 24), Position::new(5, 32)));
+        // The range of a constant selector is the one of its string
+        assert_eq!(index.get("app-badge").unwrap().selector_range, Range::new(Position::new(1, 24), Position::new(1, 33)));
+        assert!(index.contains_key("app-alert"));
+        // Selectors computed from variables are skipped
+        assert_eq!(index.component_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn incremental_edits_reparse_little_and_match_full_parses() {
+        let backend = &server();
+        let rows: String = (0..200).map(|row| format!("<li class=\"row\" [title]=\"items[{}]\">{{{{ {} }}}}</li>\n", row, row)).collect();
+        let text = format!("<ul>\n{}</ul>\n", rows);
+        let uri = open(backend, "src/app/list.component.html", &text).await;
+        let old_rope = backend.document_map.get(uri.as_str()).unwrap().clone();
+        let old_tree = backend.ast_map.get(uri.as_str()).unwrap().clone();
+        // `row` becomes `cell` on the hundredth row
+        backend.did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(100, 11), Position::new(100, 14))),
+                range_length: None,
+                text: String::from("cell"),
+            }],
+        }).await;
+        let rope = backend.document_map.get(uri.as_str()).unwrap().clone();
+        let tree = backend.ast_map.get(uri.as_str()).unwrap().clone();
+        assert!(rope.line(100).to_string().starts_with("<li class=\"cell\""));
+        assert_eq!(tree.root_node().to_sexp(), HtmlAnalyzer::new().parse(&rope, None).root_node().to_sexp());
+
+        let mut edited_tree = old_tree;
+        edited_tree.edit(&control_flow::masked_edit(&old_rope.to_string(), &rope.to_string()).unwrap());
+        let changed: usize = edited_tree.changed_ranges(&tree).map(|range| range.end_byte - range.start_byte).sum();
+        assert!(changed < 100, "{} bytes changed", changed);
+    }
+
+    #[tokio::test]
+    async fn edits_near_block_syntax_match_full_parses() {
+        let backend = &server();
+        let uri = open(backend, "src/app/list.component.html", "@f (a) {\n<b></b>\n}\n<p>x</p>\n").await;
+        let change = |version, line, character, text: &str| DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(line, character), Position::new(line, character))),
+                range_length: None,
+                text: String::from(text),
+            }],
+        };
+        // Naming the block masks its header and closing brace, a plain edit only the text
+        for (version, line, character, text) in [(2, 0, 1, "i"), (3, 3, 4, "y"), (4, 1, 3, "<i></i>"), (5, 2, 1, "}")] {
+            backend.did_change(change(version, line, character, text)).await;
+            let rope = backend.document_map.get(uri.as_str()).unwrap().clone();
+            let tree = backend.ast_map.get(uri.as_str()).unwrap().clone();
+            assert_eq!(tree.root_node().to_sexp(), HtmlAnalyzer::new().parse(&rope, None).root_node().to_sexp(), "{}", rope);
+        }
+        assert_eq!(backend.document_map.get(uri.as_str()).unwrap().to_string(), "@if (a) {\n<b><i></i></b>\n}}\n<p>xy</p>\n");
+    }
+
+    #[test]
+    fn template_files_come_from_the_component_source_roots() {
+        let backend = &server();
+        let root = folder(&[
+            ("angular.json", r#"{ "projects": { "web": { "sourceRoot": "apps/web/src" } } }"#),
+            ("apps/web/src/app/card.component.html", "<p></p>"),
+            ("src/app/stale.component.html", "<p></p>"),
+        ]);
+        let root = root.to_str().unwrap().to_string();
+        backend.workspace_roots.write().unwrap().push(root.clone());
+        let paths = |backend: &Backend| -> Vec<PathBuf> { backend.template_files().into_iter().map(|(_, path)| path).collect() };
+        assert_eq!(paths(backend), vec![Path::new(&root).join("apps/web/src/app/card.component.html")]);
+
+        // Configured component globs give the template globs
+        backend.config.write().unwrap().component_globs = Some(vec![String::from("src/**/*.ts")]);
+        assert_eq!(paths(backend), vec![Path::new(&root).join("src/app/stale.component.html")]);
+    }
+
+    #[test]
+    fn file_urls_round_trip_outside_of_src() {
+        let root = folder(&[("libs/ui/card #1.component.ts", CARD)]);
+        let path = root.join("libs/ui/card #1.component.ts");
+        let index = ComponentIndex::default();
+        let mut analyzer = ComponentAnalyzer::new().unwrap();
+        analyzer.analyze_file(&path, &DashMap::new(), &index, u64::MAX).unwrap();
+        let file_url = index.get("app-card").unwrap().file_url.clone();
+        assert_eq!(file_url.scheme(), "file");
+        assert!(file_url.path().ends_with("/libs/ui/card%20%231.component.ts"), "{}", file_url);
+        assert_eq!(file_url.fragment(), None);
+        assert_eq!(file_url.to_file_path().unwrap(), path);
+        // Relative paths make no URL
+        let error = analyzer.analyze_file(Path::new("libs/ui/card.component.ts"), &DashMap::new(), &index, u64::MAX).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn components_belong_to_their_file_rather_than_barrels() {
+        let root = folder(&[
+            ("src/app/index.ts", "export * from './card.component';\nexport { CardComponent as Card } from './card.component';"),
+            ("src/app/card.component.ts", CARD),
+        ]);
+        let index = ComponentIndex::default();
+        let mut analyzer = ComponentAnalyzer::new().unwrap();
+        for path in ComponentAnalyzer::workspace_files(root.to_str().unwrap(), &typescript_globs(DEFAULT_SOURCE_ROOT)) {
+            analyzer.analyze_file(&path, &DashMap::new(), &index, u64::MAX).unwrap();
+        }
+        assert_eq!(index.component_count(), 1);
+        assert_eq!(index.get("app-card").unwrap().file_url, Url::from_file_path(root.join("src/app/card.component.ts")).unwrap());
+    }
+
+    #[tokio::test]
+    async fn workspace_folders_are_matched_by_their_decoded_path() {
+        let backend = &server();
+        let root = folder(&[("my app/src/app/card.component.ts", CARD)]).join("my app");
+        let workspace_folder = WorkspaceFolder { uri: Url::from_directory_path(&root).unwrap(), name: String::from("my app") };
+        assert!(workspace_folder.uri.path().contains("my%20app"));
+        backend.did_change_workspace_folders(DidChangeWorkspaceFoldersParams {
+            event: WorkspaceFoldersChangeEvent { added: vec![workspace_folder.clone()], removed: Vec::new() },
+        }).await;
+        tokio::task::yield_now().await;
+        while backend.indexing_tasks.load(Ordering::SeqCst) > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(Path::new(&backend.workspace_roots.read().unwrap()[0]), root);
+        assert!(backend.components.contains_key("app-card"));
+
+        backend.did_change_workspace_folders(DidChangeWorkspaceFoldersParams {
+            event: WorkspaceFoldersChangeEvent { added: Vec::new(), removed: vec![workspace_folder] },
+        }).await;
+        assert!(backend.workspace_roots.read().unwrap().is_empty());
+        assert!(!backend.components.contains_key("app-card"));
+    }
+}
@@ -0,0 +1,61 @@
+//! Documentation of the built-in structural directives: the `<ng-template>`
+//! their microsyntax expands to, and the context variables it makes available.
+
+struct StructuralDirective {
+    attribute: &'static str,
+    expansion: &'static str,
+    /// Names and descriptions of the context variables.
+    variables: &'static [(&'static str, &'static str)],
+}
+
+static STRUCTURAL_DIRECTIVES: &[StructuralDirective] = &[
+    StructuralDirective {
+        attribute: "*ngFor",
+        expansion: "<ng-template ngFor let-item [ngForOf]=\"items\" let-i=\"index\">\n  ...\n</ng-template>",
+        variables: &[
+            ("$implicit", "the current item"),
+            ("index", "position of the item"),
+            ("count", "number of items"),
+            ("first", "whether the item is the first one"),
+            ("last", "whether the item is the last one"),
+            ("even", "whether the index is even"),
+            ("odd", "whether the index is odd"),
+        ],
+    },
+    StructuralDirective {
+        attribute: "*ngIf",
+        expansion: "<ng-template [ngIf]=\"condition\" let-value=\"ngIf\" [ngIfElse]=\"other\">\n  ...\n</ng-template>",
+        variables: &[
+            ("$implicit", "the value of the condition"),
+            ("ngIf", "the value of the condition, for `as` aliases"),
+        ],
+    },
+    StructuralDirective {
+        attribute: "*ngSwitchCase",
+        expansion: "<ng-template [ngSwitchCase]=\"value\">\n  ...\n</ng-template>",
+        variables: &[],
+    },
+    StructuralDirective {
+        attribute: "*ngSwitchDefault",
+        expansion: "<ng-template ngSwitchDefault>\n  ...\n</ng-template>",
+        variables: &[],
+    },
+    StructuralDirective {
+        attribute: "*ngTemplateOutlet",
+        expansion: "<ng-template [ngTemplateOutlet]=\"template\" [ngTemplateOutletContext]=\"context\">\n</ng-template>",
+        variables: &[],
+    },
+];
+
+/// Markdown documentation of the structural directive `attribute`, like `*ngFor`.
+pub fn hover_contents(attribute: &str) -> Option<String> {
+    let directive = STRUCTURAL_DIRECTIVES.iter().find(|directive| directive.attribute == attribute)?;
+    let mut contents = format!("`{}` expands to\n\n```html\n{}\n```", directive.attribute, directive.expansion);
+    if !directive.variables.is_empty() {
+        contents.push_str("\n\nContext variables:");
+        for (variable, description) in directive.variables {
+            contents.push_str(&format!("\n- `{}` — {}", variable, description));
+        }
+    }
+    Some(contents)
+}
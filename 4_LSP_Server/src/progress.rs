@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::notification::{Notification, Progress};
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::*;
+use tower_lsp::Client;
+
+/// Distinguishes the progress of folders being indexed at the same time.
+static NEXT_TOKEN: AtomicUsize = AtomicUsize::new(0);
+
+/// The `angular/status` notification, sent as indexing starts and ends
+/// so that clients show whether the index is complete without polling.
+pub enum StatusNotification {}
+
+impl Notification for StatusNotification {
+    type Params = Status;
+    const METHOD: &'static str = "angular/status";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// Whether some workspace folder is still being indexed.
+    pub indexing: bool,
+    pub component_count: usize,
+}
+
+/// Server initiated work done progress, shown by the client while indexing.
+/// Every call is a no-op when the client doesn't support progress.
+pub struct IndexingProgress {
+    client: Client,
+    token: Option<NumberOrString>,
+    total: usize,
+}
+
+impl IndexingProgress {
+    pub async fn begin(client: &Client, supported: bool, total: usize) -> Self {
+        let mut progress = IndexingProgress { client: client.clone(), token: None, total };
+        if !supported {
+            return progress;
+        }
+        let token = NumberOrString::String(format!("angular-lsp/indexing/{}", NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)));
+        let create = WorkDoneProgressCreateParams { token: token.clone() };
+        if let Err(e) = client.send_request::<WorkDoneProgressCreate>(create).await {
+            warn!("Error creating progress {:?}", e);
+            return progress;
+        }
+        progress.token = Some(token);
+        progress.notify(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: String::from("Indexing components"),
+            cancellable: Some(false),
+            message: Some(progress.message(0)),
+            percentage: Some(0),
+        })).await;
+        progress
+    }
+
+    pub async fn report(&self, done: usize) {
+        self.notify(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(self.message(done)),
+            percentage: Some((done * 100 / self.total.max(1)) as u32),
+        })).await;
+    }
+
+    pub async fn end(self) {
+        self.notify(WorkDoneProgress::End(WorkDoneProgressEnd { message: None })).await;
+    }
+
+    fn message(&self, done: usize) -> String {
+        format!("Indexing components ({}/{})", done, self.total)
+    }
+
+    async fn notify(&self, value: WorkDoneProgress) {
+        if let Some(token) = &self.token {
+            self.client.send_notification::<Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(value),
+            }).await;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Renaming of the inputs and outputs of components, along with the template
+//! bindings using them. An aliased property keeps its member and alias apart:
+//! renaming the member leaves templates alone, renaming the alias updates them.
+
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Point};
+
+use crate::index::ComponentIndex;
+use crate::{bindable_components, node_text, parse_binding, range_contains, to_position, walk_tree, BindingKind, Component, Property};
+
+/// An input or output to rename, with the component declaring it.
+pub struct PropertyTarget {
+    pub component: Component,
+    pub property: Property,
+    pub output: bool,
+    /// Whether the rename starts from the class member rather than from the name templates bind to.
+    pub on_member: bool,
+}
+
+/// What renaming a property changes: edits of the component file,
+/// and the bindings to rename in templates.
+pub struct PropertyRename {
+    pub declaration_edits: Vec<TextEdit>,
+    pub bindings: Vec<BindingRename>,
+}
+
+pub struct BindingRename {
+    pub name: String,
+    pub output: bool,
+    pub new_name: String,
+}
+
+/// Checks that `name` can be used as a class member name.
+pub fn is_valid_property_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Name an attribute binds, whether it's an output, and the length of the decorations before it.
+/// A plain attribute sets the input of the same name.
+fn bound_name(attribute: &str) -> (&str, bool, usize) {
+    match parse_binding(attribute) {
+        Some((BindingKind::TwoWay, name)) => (name, false, 2),
+        Some((BindingKind::Property, name)) => (name, false, 1),
+        Some((BindingKind::Event, name)) => (name, true, 1),
+        None => (attribute, false, 0),
+    }
+}
+
+/// Range of the name bound by `attribute_name`, without its decorations.
+pub fn name_range(attribute_name: &Node, rope: &Rope) -> Option<Range> {
+    let attribute = node_text(attribute_name, rope)?;
+    let (name, _, prefix) = bound_name(&attribute);
+    let position = |column: usize| {
+        let point = Point { row: attribute_name.start_position().row, column: attribute_name.start_position().column + column };
+        to_position(rope, attribute_name.start_byte() + column, point)
+    };
+    Some(Range::new(position(prefix), position(prefix + name.len())))
+}
+
+fn find_property<'a>(component: &'a Component, name: &str, output: bool) -> Option<&'a Property> {
+    let properties = if output { &component.outputs } else { &component.inputs };
+    properties.iter().find(|property| property.binding_name() == name)
+}
+
+/// The property that `attribute_name` binds on its element.
+pub fn template_target(attribute_name: &Node, rope: &Rope, components: &ComponentIndex) -> Option<PropertyTarget> {
+    let attribute = node_text(attribute_name, rope)?;
+    let (name, output, _) = bound_name(&attribute);
+    let start_tag = attribute_name.parent()?.parent()?;
+    bindable_components(&start_tag, rope, components).into_iter()
+        .find_map(|component| {
+            let property = find_property(&component, name, output)?.clone();
+            Some(PropertyTarget { component, property, output, on_member: false })
+        })
+}
+
+/// The property whose member name or alias is at `position` of the component file `uri`.
+pub fn declaration_target(uri: &Url, position: Position, components: &ComponentIndex) -> Option<PropertyTarget> {
+    components.iter()
+        .filter(|component| component.file_url == *uri)
+        .find_map(|component| {
+            let properties = component.inputs.iter().map(|input| (input, false))
+                .chain(component.outputs.iter().map(|output| (output, true)));
+            for (property, output) in properties {
+                let on_member = range_contains(&property.range, position);
+                if on_member || property.alias_range.is_some_and(|range| range_contains(&range, position)) {
+                    return Some(PropertyTarget { component: component.clone(), property: property.clone(), output, on_member });
+                }
+            }
+            None
+        })
+}
+
+/// Range of the name that renaming `target` starts from.
+pub fn declaration_range(target: &PropertyTarget) -> Range {
+    match (target.on_member, target.property.alias_range) {
+        (false, Some(alias_range)) => alias_range,
+        _ => target.property.range,
+    }
+}
+
+/// Outputs that a `model()` implies, named after its input.
+fn is_implied(property: &Property) -> bool {
+    property.alias.is_some() && property.alias_range.is_none()
+}
+
+/// Renames `target` to `new_name`, none for the output of a model, renamed along with its input.
+pub fn rename(target: &PropertyTarget, new_name: &str) -> Option<PropertyRename> {
+    let property = &target.property;
+    if target.output && is_implied(property) {
+        return None;
+    }
+    let mut declaration_edits = Vec::new();
+    if property.alias.is_none() || target.on_member {
+        declaration_edits.push(TextEdit::new(property.range, String::from(new_name)));
+    }
+    let mut bindings = Vec::new();
+    if property.alias.is_none() || !target.on_member {
+        if let Some(alias_range) = property.alias_range {
+            declaration_edits.push(TextEdit::new(alias_range, String::from(new_name)));
+        }
+        bindings.push(BindingRename {
+            name: String::from(property.binding_name()),
+            output: target.output,
+            new_name: String::from(new_name),
+        });
+        let model_output = target.component.outputs.iter()
+            .find(|output| !target.output && is_implied(output) && output.range == property.range);
+        if let Some(model_output) = model_output {
+            bindings.push(BindingRename {
+                name: String::from(model_output.binding_name()),
+                output: true,
+                new_name: format!("{}Change", new_name),
+            });
+        }
+    }
+    Some(PropertyRename { declaration_edits, bindings })
+}
+
+/// Ranges of the names of the attributes binding `binding` on the elements that `selector` applies to.
+pub fn binding_references(root: &Node, rope: &Rope, components: &ComponentIndex, selector: &str, binding: &BindingRename) -> Vec<Range> {
+    let mut ranges = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.kind() != "start_tag" && node.kind() != "self_closing_tag" {
+            return;
+        }
+        if !bindable_components(&node, rope, components).iter().any(|component| component.selector == selector) {
+            return;
+        }
+        let mut cursor = node.walk();
+        let attribute_names: Vec<Node> = node.named_children(&mut cursor)
+            .filter(|child| child.kind() == "attribute")
+            .filter_map(|attribute| attribute.named_child(0))
+            .collect();
+        for attribute_name in attribute_names {
+            let Some(attribute) = node_text(&attribute_name, rope) else {
+                continue;
+            };
+            let (name, output, _) = bound_name(&attribute);
+            if name == binding.name && output == binding.output {
+                ranges.extend(name_range(&attribute_name, rope));
+            }
+        }
+    });
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{index, template};
+
+    const CARD: &str = r#"
+@Component({ selector: 'app-card', template: '' })
+export class CardComponent {
+  @Input() title: string;
+  @Output() closed = new EventEmitter<void>();
+}"#;
+
+    #[test]
+    fn binding_ranges_leave_out_decorations_and_count_utf16_code_units() {
+        let components = index(&[("card.component.ts", CARD)]);
+        let (rope, tree) = template("<p>😀</p><app-card [title]=\"t\" (closed)=\"c()\"></app-card><app-card title=\"é\"></app-card>");
+        let title = BindingRename { name: String::from("title"), output: false, new_name: String::from("heading") };
+        let range = |start, end| Range::new(Position::new(0, start), Position::new(0, end));
+        assert_eq!(binding_references(&tree.root_node(), &rope, &components, "app-card", &title), vec![
+            range(20, 25),
+            range(67, 72),
+        ]);
+        let closed = BindingRename { name: String::from("closed"), output: true, new_name: String::from("dismissed") };
+        assert_eq!(binding_references(&tree.root_node(), &rope, &components, "app-card", &closed), vec![range(32, 38)]);
+    }
+}
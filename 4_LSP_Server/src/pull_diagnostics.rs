@@ -0,0 +1,44 @@
+//! Pull diagnostics of LSP 3.17, which lsp-types doesn't know yet: the
+//! `textDocument/diagnostic` request and the registration advertising it.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tower_lsp::lsp_types::*;
+
+pub const METHOD: &str = "textDocument/diagnostic";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentDiagnosticParams {
+    pub text_document: TextDocumentIdentifier,
+    /// Result id of the report the client last received for the document.
+    pub previous_result_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DocumentDiagnosticReport {
+    #[serde(rename_all = "camelCase")]
+    Full {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result_id: Option<String>,
+        items: Vec<Diagnostic>,
+    },
+    /// The report of `result_id` still holds.
+    #[serde(rename_all = "camelCase")]
+    Unchanged { result_id: String },
+}
+
+/// Registers the `diagnostic` capability, missing from the server capabilities of lsp-types.
+pub fn registration() -> Registration {
+    Registration {
+        id: String::from(METHOD),
+        method: String::from(METHOD),
+        register_options: Some(json!({
+            "documentSelector": null,
+            // Templates depend on the components that other files declare
+            "interFileDependencies": true,
+            "workspaceDiagnostics": false,
+        })),
+    }
+}
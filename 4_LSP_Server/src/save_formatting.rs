@@ -0,0 +1,115 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::index::ComponentIndex;
+use crate::{bindable_components, byte_to_point, node_text, to_position, walk_tree};
+
+/// Built-in directives and their inputs and outputs, in their canonical spelling.
+static BUILTIN_BINDINGS: &[&str] = &[
+    "ngClass", "ngComponentOutlet", "ngFor", "ngForOf", "ngForTrackBy", "ngIf", "ngIfElse", "ngModel",
+    "ngModelChange", "ngModelOptions", "ngNonBindable", "ngPlural", "ngPluralCase", "ngStyle", "ngSubmit",
+    "ngSwitch", "ngSwitchCase", "ngSwitchDefault", "ngTemplateOutlet", "ngTemplateOutletContext",
+];
+
+/// Byte length of the decorations before the name of a binding attribute, and that name.
+fn binding_name(attribute: &str) -> Option<(usize, &str)> {
+    let prefix = ["[(", "[", "(", "*"].iter().find(|prefix| attribute.starts_with(**prefix))?;
+    let name = attribute[prefix.len()..].trim_end_matches([']', ')']);
+    Some((prefix.len(), name))
+}
+
+fn byte_range(rope: &Rope, start: usize, end: usize) -> Range {
+    Range::new(to_position(rope, start, byte_to_point(rope, start)), to_position(rope, end, byte_to_point(rope, end)))
+}
+
+/// Edits tidying the binding attributes of a template, so that saving it again changes nothing:
+/// no whitespace around their `=`, and the spelling of the inputs and outputs they bind.
+pub fn tidy_bindings(root: &Node, rope: &Rope, components: &ComponentIndex) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.kind() != "start_tag" && node.kind() != "self_closing_tag" {
+            return;
+        }
+        let bindable = bindable_components(&node, rope, components);
+        let known_names: Vec<&str> = BUILTIN_BINDINGS.iter().copied()
+            .chain(bindable.iter()
+                .flat_map(|component| component.inputs.iter().chain(component.outputs.iter()))
+                .map(|property| property.binding_name()))
+            .collect();
+        let mut cursor = node.walk();
+        let attributes: Vec<Node> = node.named_children(&mut cursor)
+            .filter(|child| child.kind() == "attribute")
+            .collect();
+        for attribute in attributes {
+            let Some(attribute_name) = attribute.named_child(0) else {
+                continue;
+            };
+            let Some((prefix, name)) = node_text(&attribute_name, rope).as_deref().and_then(binding_name)
+                .map(|(prefix, name)| (prefix, String::from(name))) else {
+                continue;
+            };
+            // A name known in several spellings, like inputs `value` and `Value`, is left as written
+            let mut spellings: Vec<&str> = known_names.iter().copied()
+                .filter(|known| known.eq_ignore_ascii_case(&name))
+                .collect();
+            spellings.sort_unstable();
+            spellings.dedup();
+            if let [canonical] = spellings[..] {
+                if canonical != name {
+                    let start = attribute_name.start_byte() + prefix;
+                    edits.push(TextEdit::new(byte_range(rope, start, start + name.len()), String::from(canonical)));
+                }
+            }
+            if let Some(value) = attribute.named_child(1) {
+                let Some(between) = rope.get_byte_slice(attribute_name.end_byte()..value.start_byte()) else {
+                    continue;
+                };
+                if between != "=" && between.chars().all(|c| c == '=' || c.is_whitespace()) {
+                    edits.push(TextEdit::new(byte_range(rope, attribute_name.end_byte(), value.start_byte()), String::from("=")));
+                }
+            }
+        }
+    });
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply_change;
+    use crate::testing::{index, template};
+
+    fn tidied(text: &str, components: &ComponentIndex) -> (String, usize) {
+        let (mut rope, tree) = template(text);
+        let mut edits = tidy_bindings(&tree.root_node(), &rope, components);
+        // From the last, so that the earlier ranges still apply
+        edits.sort_by_key(|edit| std::cmp::Reverse((edit.range.start.line, edit.range.start.character)));
+        for edit in &edits {
+            apply_change(&mut rope, edit.range, &edit.new_text);
+        }
+        (rope.to_string(), edits.len())
+    }
+
+    #[test]
+    fn tidied_bindings_stay_as_they_are() {
+        let components = ComponentIndex::default();
+        let (text, edits) = tidied(r#"<div [ngclass] = "x" (NgModelChange)="y">"#, &components);
+        assert_eq!(text, r#"<div [ngClass]="x" (ngModelChange)="y">"#);
+        assert_eq!(edits, 3);
+        assert_eq!(tidied(&text, &components), (text, 0));
+    }
+
+    #[test]
+    fn names_known_in_several_spellings_keep_theirs() {
+        let components = index(&[("src/app/field.component.ts", r#"
+@Component({ selector: 'app-field', template: '' })
+export class FieldComponent {
+  @Input() value: string;
+  @Input() Value: string;
+  @Input() label: string;
+}"#)]);
+        let (text, _) = tidied(r#"<app-field [VALUE]="x" [Label]="y"></app-field>"#, &components);
+        assert_eq!(text, r#"<app-field [VALUE]="x" [label]="y"></app-field>"#);
+    }
+}
@@ -0,0 +1,58 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::to_range;
+
+/// Ranges of the node at `offset` and of its ancestors, innermost first,
+/// as the editor expands its selection through them.
+pub fn selection_range(root: &Node, offset: usize, rope: &Rope) -> Option<SelectionRange> {
+    let mut ranges: Vec<Range> = Vec::new();
+    let mut node = root.named_descendant_for_byte_range(offset, offset);
+    while let Some(current) = node {
+        let range = to_range(rope, current.range());
+        // A node spanning the same text as its child adds no step
+        if ranges.last() != Some(&range) {
+            ranges.push(range);
+        }
+        node = current.parent();
+    }
+    ranges.into_iter().rev().fold(None, |parent, range| Some(SelectionRange {
+        range,
+        parent: parent.map(Box::new),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::template;
+
+    fn chain(selection: Option<SelectionRange>) -> Vec<Range> {
+        let mut ranges = Vec::new();
+        let mut selection = selection.map(Box::new);
+        while let Some(current) = selection {
+            ranges.push(current.range);
+            selection = current.parent;
+        }
+        ranges
+    }
+
+    fn range(start: u32, end: u32) -> Range {
+        Range::new(Position::new(0, start), Position::new(0, end))
+    }
+
+    #[test]
+    fn selection_grows_from_the_attribute_to_the_outer_elements() {
+        let (rope, tree) = template("<ul><li class=\"item\">One</li></ul>");
+        // Inside the attribute value
+        assert_eq!(chain(selection_range(&tree.root_node(), 16, &rope)), vec![
+            range(15, 19),
+            range(14, 20),
+            range(8, 20),
+            range(4, 21),
+            range(4, 29),
+            range(0, 34),
+        ]);
+    }
+}
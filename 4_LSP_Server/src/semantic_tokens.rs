@@ -0,0 +1,93 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::{byte_to_point, node_text, to_position, to_range, walk_tree};
+
+/// Token types of the legend, indexed by the emitted tokens.
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::EVENT,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::OPERATOR,
+];
+
+const PROPERTY: u32 = 0;
+const EVENT: u32 = 1;
+const MACRO: u32 = 2;
+const OPERATOR: u32 = 3;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: Vec::new(),
+    }
+}
+
+/// Token type of an attribute name, by its binding decorations.
+fn attribute_token_type(attribute: &str) -> Option<u32> {
+    if attribute.starts_with('[') {
+        Some(PROPERTY)
+    } else if attribute.starts_with('(') {
+        Some(EVENT)
+    } else if attribute.starts_with('*') {
+        Some(MACRO)
+    } else {
+        None
+    }
+}
+
+/// Single line ranges of the bindings and interpolation delimiters of a template,
+/// in document order, along with their token type.
+pub fn template_tokens(root: &Node, rope: &Rope) -> Vec<(Range, u32)> {
+    let mut tokens = Vec::new();
+    walk_tree(root, &mut |node| {
+        match node.kind() {
+            "attribute_name" => {
+                if let Some(token_type) = node_text(&node, rope).as_deref().and_then(attribute_token_type) {
+                    tokens.push((to_range(rope, node.range()), token_type));
+                }
+            }
+            "text" | "attribute_value" => {
+                let Some(text) = node_text(&node, rope) else {
+                    return;
+                };
+                let mut delimiters: Vec<usize> = text.match_indices("{{").chain(text.match_indices("}}"))
+                    .map(|(index, _)| index)
+                    .collect();
+                delimiters.sort();
+                for index in delimiters {
+                    let byte = node.start_byte() + index;
+                    let start = to_position(rope, byte, byte_to_point(rope, byte));
+                    let end = Position { character: start.character + 2, ..start };
+                    tokens.push((Range::new(start, end), OPERATOR));
+                }
+            }
+            _ => {}
+        }
+    });
+    tokens
+}
+
+/// Encodes sorted tokens relatively to each other, as the protocol requires.
+pub fn encode(tokens: &[(Range, u32)]) -> Vec<SemanticToken> {
+    let mut previous = Position::default();
+    tokens.iter()
+        .map(|(range, token_type)| {
+            let delta_line = range.start.line - previous.line;
+            let delta_start = if delta_line == 0 {
+                range.start.character - previous.character
+            } else {
+                range.start.character
+            };
+            previous = range.start;
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: range.end.character - range.start.character,
+                token_type: *token_type,
+                token_modifiers_bitset: 0,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,45 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::index::ComponentIndex;
+use crate::{find_node, node_text, parse_binding, BindingKind, ComponentKind, Property};
+
+/// Documents the `$event` of the output whose handler is under `offset`.
+pub fn signature_help(root: &Node, offset: usize, rope: &Rope, components: &ComponentIndex) -> Option<SignatureHelp> {
+    let attribute = find_node(root, offset, vec!["attribute"])?;
+    let attribute_name = attribute.named_child(0)?;
+    let value = attribute.named_child(1)?;
+    if offset <= value.start_byte() || offset >= value.end_byte() {
+        return None;
+    }
+    let attribute_text = node_text(&attribute_name, rope)?;
+    let Some((BindingKind::Event, name)) = parse_binding(&attribute_text) else {
+        return None;
+    };
+    let find_output = |outputs: &[Property]| outputs.iter().find(|output| output.binding_name() == name).cloned();
+    let tag_name = attribute.parent()?.named_child(0).and_then(|tag_name| node_text(&tag_name, rope))?;
+    let (output, owner) = components.get(&tag_name)
+        .and_then(|component| find_output(&component.outputs).map(|output| (output, format!("<{}>", component.selector))))
+        .or_else(|| components.iter()
+            .filter(|directive| directive.kind == ComponentKind::Directive)
+            .find_map(|directive| find_output(&directive.outputs).map(|output| (output, directive.selector.clone()))))?;
+
+    let parameter = match &output.value_type {
+        Some(value_type) => format!("$event: {}", value_type),
+        None => String::from("$event"),
+    };
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label: parameter.clone(),
+            documentation: Some(Documentation::String(format!("Payload of the `{}` output of `{}`", name, owner))),
+            parameters: Some(vec![ParameterInformation {
+                label: ParameterLabel::Simple(parameter),
+                documentation: None,
+            }]),
+            active_parameter: Some(0),
+        }],
+        active_signature: Some(0),
+        active_parameter: Some(0),
+    })
+}
@@ -0,0 +1,154 @@
+//! Class names defined by the `styleUrls` of a component, completed in its own template.
+
+use std::fs;
+
+use log::debug;
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::{find_node, node_text, Component};
+
+/// Stylesheet without its `/* */` comments.
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = rest[start + 2..].split_once("*/").map_or("", |(_, after)| after);
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Names of the `.class-name` selectors of a stylesheet, CSS or SCSS and Less nesting alike.
+pub fn class_names(css: &str) -> Vec<String> {
+    let css = strip_comments(css);
+    let mut names: Vec<String> = Vec::new();
+    // Selectors are what comes before a block, declarations end with a `;` or the block
+    let mut prelude_start = 0;
+    for (index, c) in css.char_indices() {
+        match c {
+            ';' | '}' => prelude_start = index + 1,
+            '{' => {
+                let prelude = css[prelude_start..index].trim();
+                if !prelude.starts_with('@') {
+                    for name in prelude_class_names(prelude) {
+                        if !names.contains(&name) {
+                            names.push(name);
+                        }
+                    }
+                }
+                prelude_start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Class names of a selector list, leaving out what attribute selectors match.
+fn prelude_class_names(prelude: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut in_brackets = false;
+    for (index, c) in prelude.char_indices() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            '.' if !in_brackets => {
+                let name: String = prelude[index + 1..].chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                    .collect();
+                if name.starts_with(|c: char| c.is_alphabetic() || c == '-' || c == '_') {
+                    names.push(name);
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Class names of every style file of `owner`, with the file defining them.
+/// Style files that can't be read are skipped.
+fn owner_classes(owner: &Component) -> Vec<(String, String)> {
+    owner.style_urls.iter()
+        .filter_map(|style_url| {
+            let contents = style_url.to_file_path().ok().and_then(|path| fs::read_to_string(path).ok());
+            if contents.is_none() {
+                debug!("Can't read the style file {} of {}", style_url, owner.class_name);
+            }
+            let file_name = style_url.path_segments()?.next_back().map(String::from)?;
+            Some((contents?, file_name))
+        })
+        .flat_map(|(contents, file_name)| class_names(&contents).into_iter()
+            .map(move |name| (name, file_name.clone())))
+        .collect()
+}
+
+/// Completions of the classes of the styles of `owner`, when `offset` is in the value
+/// of a `class` attribute, or in a string of an `[ngClass]` binding.
+pub fn completions(start_tag: &Node, offset: usize, rope: &Rope, owner: Option<&Component>) -> Option<Vec<CompletionItem>> {
+    let quoted = find_node(start_tag, offset, vec![ "quoted_attribute_value" ])?;
+    if offset <= quoted.start_byte() || offset >= quoted.end_byte() {
+        return None;
+    }
+    let attribute_name = node_text(&quoted.parent()?.named_child(0)?, rope)?;
+    let typed = rope.get_byte_slice(quoted.start_byte() + 1..offset).map(String::from)?;
+    let typed = match attribute_name.as_str() {
+        "class" => typed.as_str(),
+        // Class names of an expression are in its string literals, or its object keys
+        "[ngClass]" if typed.matches('\'').count() % 2 == 1 => typed.rsplit('\'').next().unwrap_or_default(),
+        _ => return None,
+    };
+    let typed = typed.rsplit(char::is_whitespace).next().unwrap_or_default();
+    let value = node_text(&quoted, rope)?;
+    let present: Vec<&str> = value.trim_matches(|c| c == '"' || c == '\'').split_whitespace().collect();
+    let completions = owner.map(owner_classes).unwrap_or_default().into_iter()
+        .filter(|(name, _)| name.starts_with(typed) && (name == typed || !present.contains(&name.as_str())))
+        .map(|(name, file_name)| CompletionItem {
+            label: name,
+            kind: Some(CompletionItemKind::VALUE),
+            detail: Some(file_name),
+            ..Default::default()
+        })
+        .collect();
+    Some(completions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ComponentIndex;
+    use crate::testing::{folder, template};
+    use crate::ComponentAnalyzer;
+
+    #[test]
+    fn class_selectors_are_found_out_of_comments_and_attributes() {
+        let css = ".btn, .btn-primary:hover { color: red; }\n/* .old {} */\n[data-x='.y'] .card { .title { } }\n@media (max-width: 1.5em) { .wide {} }";
+        assert_eq!(class_names(css), ["btn", "btn-primary", "card", "title", "wide"]);
+    }
+
+    #[test]
+    fn classes_come_from_the_style_files_of_the_component() {
+        let root = folder(&[("src/app/card.component.css", ".btn { }\n.card-body { }")]);
+        let components = ComponentIndex::default();
+        let url = Url::from_file_path(root.join("src/app/card.component.ts")).unwrap();
+        ComponentAnalyzer::new().unwrap().analyze_contents(r#"
+@Component({ selector: 'app-card', template: '', styleUrls: ['./card.component.css', './missing.component.css'] })
+export class CardComponent {}"#, &url, &components);
+        let owner = components.get("app-card").unwrap().clone();
+
+        let text = "<div class=\"card-body b\"></div>";
+        let (rope, tree) = template(text);
+        let start_tag = tree.root_node().named_child(0).unwrap().named_child(0).unwrap();
+        let offset = text.find(" b\"").unwrap() + 2;
+        let items = completions(&start_tag, offset, &rope, Some(&owner)).unwrap();
+        let items: Vec<(&str, Option<&str>)> = items.iter()
+            .map(|item| (item.label.as_str(), item.detail.as_deref()))
+            .collect();
+        assert_eq!(items, [("btn", Some("card.component.css"))]);
+        // Out of class attributes
+        assert!(completions(&start_tag, 2, &rope, Some(&owner)).is_none());
+    }
+}
@@ -0,0 +1,76 @@
+//! Outline of a template: its elements as nested symbols, components named after their class.
+
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::index::ComponentIndex;
+use crate::{node_text, to_range};
+
+fn is_element(node: &Node) -> bool {
+    matches!(node.kind(), "element" | "script_element" | "style_element")
+}
+
+#[allow(deprecated)]
+fn element_symbol(element: &Node, rope: &Rope, components: &ComponentIndex) -> Option<DocumentSymbol> {
+    let tag_name = element.named_child(0)?.named_child(0)?;
+    let name = node_text(&tag_name, rope)?;
+    let (name, detail, kind) = match components.get(&name) {
+        Some(component) => (component.class_name.clone(), Some(name), SymbolKind::CLASS),
+        None => (name, None, SymbolKind::FIELD),
+    };
+    Some(DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: to_range(rope, element.range()),
+        selection_range: to_range(rope, tag_name.range()),
+        children: Some(element_symbols(element, rope, components)),
+    })
+}
+
+/// Symbols of the elements under `node`, looking through the nodes that aren't elements.
+pub fn element_symbols(node: &Node, rope: &Rope, components: &ComponentIndex) -> Vec<DocumentSymbol> {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.named_children(&mut cursor).collect();
+    children.iter()
+        .flat_map(|child| if is_element(child) {
+            element_symbol(child, rope, components).into_iter().collect()
+        } else {
+            element_symbols(child, rope, components)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{index, template};
+
+    /// Names and kinds of `symbols` and of their children, at their depth.
+    fn outline(symbols: &[DocumentSymbol], depth: usize, lines: &mut Vec<(usize, String, SymbolKind)>) {
+        for symbol in symbols {
+            lines.push((depth, symbol.name.clone(), symbol.kind));
+            outline(symbol.children.as_deref().unwrap_or_default(), depth + 1, lines);
+        }
+    }
+
+    #[test]
+    fn symbols_nest_like_elements_components_by_their_class() {
+        let components = index(&[
+            ("card.component.ts", "@Component({ selector: 'app-card', template: '' })\nexport class CardComponent {}"),
+            ("list.component.ts", "@Component({ selector: 'app-list', template: '' })\nexport class ListComponent {}"),
+        ]);
+        let (rope, tree) = template("<app-list>\n  <li><app-card>{{ title }}</app-card></li>\n</app-list>\n<p></p>");
+        let mut lines = Vec::new();
+        outline(&element_symbols(&tree.root_node(), &rope, &components), 0, &mut lines);
+        assert_eq!(lines, [
+            (0, String::from("ListComponent"), SymbolKind::CLASS),
+            (1, String::from("li"), SymbolKind::FIELD),
+            (2, String::from("CardComponent"), SymbolKind::CLASS),
+            (0, String::from("p"), SymbolKind::FIELD),
+        ]);
+    }
+}
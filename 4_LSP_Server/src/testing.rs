@@ -0,0 +1,74 @@
+//! Helpers of the unit tests, building templates, indexes and servers out of source text.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ropey::Rope;
+use tower_lsp::lsp_types::*;
+use tower_lsp::{Client, LanguageServer, LspService};
+use tree_sitter::Tree;
+
+use crate::index::ComponentIndex;
+use crate::{Backend, ComponentAnalyzer, HtmlAnalyzer};
+
+static NEXT_FOLDER: AtomicUsize = AtomicUsize::new(0);
+
+/// URL of `path` in a workspace that only exists for the tests.
+pub fn file_url(path: &str) -> Url {
+    Url::parse(&format!("file:///workspace/{}", path)).unwrap()
+}
+
+/// A template along with its HTML tree, control flow blocks masked as for the documents.
+pub fn template(text: &str) -> (Rope, Tree) {
+    let rope = Rope::from_str(text);
+    let tree = HtmlAnalyzer::new().parse(&rope, None);
+    (rope, tree)
+}
+
+/// Index of the TypeScript files given by path and contents.
+pub fn index(files: &[(&str, &str)]) -> ComponentIndex {
+    let index = ComponentIndex::default();
+    let mut analyzer = ComponentAnalyzer::new().unwrap();
+    for (path, contents) in files {
+        analyzer.analyze_contents(contents, &file_url(path), &index);
+    }
+    index
+}
+
+/// Empty folder of its own under the temporary directory, holding the `files` given by relative path.
+pub fn folder(files: &[(&str, &str)]) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("angular-lsp-{}-{}", std::process::id(), NEXT_FOLDER.fetch_add(1, Ordering::SeqCst)));
+    let _ = fs::remove_dir_all(&folder);
+    for (path, contents) in files {
+        let path = folder.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    folder
+}
+
+/// A server whose client drops every message, as the service never sees it initialized.
+pub fn server() -> Backend {
+    let mut client = None;
+    LspService::new(|service_client: Client| {
+        client = Some(service_client.clone());
+        Backend::new(service_client)
+    });
+    Backend::new(client.unwrap())
+}
+
+/// Opens the document at `path` of the test workspace with `text`, returning its URL.
+pub async fn open(server: &Backend, path: &str, text: &str) -> Url {
+    let uri = file_url(path);
+    open_url(server, &uri, text).await;
+    uri
+}
+
+/// Opens the document at `uri` with `text`, a TypeScript one by its extension else a template.
+pub async fn open_url(server: &Backend, uri: &Url, text: &str) {
+    let language_id = String::from(if uri.path().ends_with(".ts") { "typescript" } else { "html" });
+    server.did_open(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(uri.clone(), language_id, 1, String::from(text)),
+    }).await;
+}
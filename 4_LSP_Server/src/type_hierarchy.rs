@@ -0,0 +1,112 @@
+//! Type hierarchy of LSP 3.17, which lsp-types doesn't know yet: the component
+//! a component extends and the components extending it, as far as the index knows.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tower_lsp::lsp_types::*;
+
+use crate::index::ComponentIndex;
+use crate::Component;
+
+pub const PREPARE_METHOD: &str = "textDocument/prepareTypeHierarchy";
+pub const SUPERTYPES_METHOD: &str = "typeHierarchy/supertypes";
+pub const SUBTYPES_METHOD: &str = "typeHierarchy/subtypes";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeHierarchyPrepareParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeHierarchyItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: Url,
+    /// Range of the whole class declaration.
+    pub range: Range,
+    /// Range of the class name.
+    pub selection_range: Range,
+}
+
+/// Parameters of both the `supertypes` and `subtypes` requests.
+#[derive(Debug, Deserialize)]
+pub struct TypeHierarchyItemParams {
+    pub item: TypeHierarchyItem,
+}
+
+pub fn component_item(component: &Component) -> TypeHierarchyItem {
+    TypeHierarchyItem {
+        name: component.class_name.clone(),
+        kind: SymbolKind::CLASS,
+        detail: Some(component.selector.clone()),
+        uri: component.file_url.clone(),
+        range: component.declaration_range,
+        selection_range: component.class_name_range,
+    }
+}
+
+/// Items of the indexed components with the class name `name`, qualified like `base.BaseComponent` or not.
+fn items_named(name: &str, components: &ComponentIndex) -> Vec<TypeHierarchyItem> {
+    let name = name.rsplit('.').next().unwrap_or(name);
+    components.iter()
+        .filter(|component| component.class_name == name)
+        .map(|component| component_item(&component))
+        .collect()
+}
+
+/// The component that the class of `item` extends, when it's indexed.
+pub fn supertypes(item: &TypeHierarchyItem, components: &ComponentIndex) -> Vec<TypeHierarchyItem> {
+    let super_class = components.iter()
+        .find(|component| component.class_name == item.name && component.file_url == item.uri)
+        .and_then(|component| component.super_class.clone());
+    super_class.map(|super_class| items_named(&super_class, components)).unwrap_or_default()
+}
+
+/// The components whose class directly extends the class of `item`.
+pub fn subtypes(item: &TypeHierarchyItem, components: &ComponentIndex) -> Vec<TypeHierarchyItem> {
+    components.iter()
+        .filter(|component| component.super_class.as_deref()
+            .is_some_and(|super_class| super_class.rsplit('.').next() == Some(item.name.as_str())))
+        .map(|component| component_item(&component))
+        .collect()
+}
+
+/// Registers the `typeHierarchy` capability, missing from the server capabilities of lsp-types.
+pub fn registration() -> Registration {
+    Registration {
+        id: String::from(PREPARE_METHOD),
+        method: String::from(PREPARE_METHOD),
+        register_options: Some(json!({ "documentSelector": null })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::index;
+
+    fn names(items: Vec<TypeHierarchyItem>) -> Vec<String> {
+        items.into_iter().map(|item| item.name).collect()
+    }
+
+    #[test]
+    fn hierarchy_goes_through_both_levels_of_inheritance() {
+        let components = index(&[
+            ("base.component.ts", "@Component({ selector: 'app-base', template: '' })\nexport class BaseComponent {}"),
+            ("field.component.ts", "@Component({ selector: 'app-field', template: '' })\nexport class FieldComponent extends base.BaseComponent {}"),
+            ("date-field.component.ts", "@Component({ selector: 'app-date-field', template: '' })\nexport class DateFieldComponent extends FieldComponent {}"),
+        ]);
+        let item = |selector: &str| component_item(&components.get(selector).unwrap());
+        assert_eq!(names(supertypes(&item("app-date-field"), &components)), ["FieldComponent"]);
+        assert_eq!(names(supertypes(&item("app-field"), &components)), ["BaseComponent"]);
+        assert!(supertypes(&item("app-base"), &components).is_empty());
+        assert_eq!(names(subtypes(&item("app-base"), &components)), ["FieldComponent"]);
+        assert_eq!(names(subtypes(&item("app-field"), &components)), ["DateFieldComponent"]);
+        assert!(subtypes(&item("app-date-field"), &components).is_empty());
+    }
+}
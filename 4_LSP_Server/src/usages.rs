@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::index::element_name;
+use crate::{attribute_selector, element_name_range, node_text, parse_binding, walk_tree, Component};
+
+#[derive(Debug, Deserialize)]
+pub struct SelectorUsagesParams {
+    pub selector: String,
+}
+
+/// Answer to the `angular/selectorUsages` request: the component, and the templates using it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectorUsages {
+    pub selector: String,
+    pub class_name: String,
+    /// Class name of the component, in its file.
+    pub location: Location,
+    pub files: Vec<FileUsages>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileUsages {
+    pub uri: Url,
+    pub count: usize,
+    pub ranges: Vec<Range>,
+}
+
+/// Ranges of the opening tags of `selector` elements, one per usage unlike references.
+pub fn element_usages(root: &Node, rope: &Rope, selector: &str) -> Vec<Range> {
+    let mut ranges = Vec::new();
+    walk_tree(root, &mut |node| {
+        let opening = node.parent()
+            .is_some_and(|parent| parent.kind() == "start_tag" || parent.kind() == "self_closing_tag");
+        let Some((name, range)) = element_name_range(&node, rope).filter(|_| node.kind() == "tag_name" && opening) else {
+            return;
+        };
+        if name == selector {
+            ranges.push(range);
+        }
+    });
+    ranges
+}
+
+/// Groups the usages of `component` by file, files and usages in order.
+pub fn selector_usages(component: &Component, locations: Vec<Location>) -> SelectorUsages {
+    let mut files: BTreeMap<Url, Vec<Range>> = BTreeMap::new();
+    for location in locations {
+        files.entry(location.uri).or_default().push(location.range);
+    }
+    SelectorUsages {
+        selector: component.selector.clone(),
+        class_name: component.class_name.clone(),
+        location: Location::new(component.file_url.clone(), component.class_name_range),
+        files: files.into_iter()
+            .map(|(uri, mut ranges)| {
+                ranges.sort_by_key(|range| range.start);
+                FileUsages { uri, count: ranges.len(), ranges }
+            })
+            .collect(),
+    }
+}
+
+/// A component that no template uses, in the answer of `angular.findUnusedComponents`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnusedComponent {
+    pub selector: String,
+    pub class_name: String,
+    pub location: Location,
+}
+
+/// Adds the tag names of a template, and the names its attributes bind, to `names`.
+pub fn used_names(root: &Node, rope: &Rope, names: &mut HashSet<String>) {
+    walk_tree(root, &mut |node| {
+        let Some(name) = node_text(&node, rope) else {
+            return;
+        };
+        match node.kind() {
+            "tag_name" => {
+                names.insert(String::from(element_name(&name)));
+            }
+            "attribute_name" => {
+                let bound = parse_binding(&name).map(|(_, bound)| String::from(bound));
+                names.insert(bound.unwrap_or(name));
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Adds the number of elements of each name of a template to `counts`, namespaces left out.
+pub fn element_counts(root: &Node, rope: &Rope, counts: &mut HashMap<String, usize>) {
+    walk_tree(root, &mut |node| {
+        let opening = node.parent()
+            .is_some_and(|parent| parent.kind() == "start_tag" || parent.kind() == "self_closing_tag");
+        if let Some((name, _)) = element_name_range(&node, rope).filter(|_| node.kind() == "tag_name" && opening) {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+    });
+}
+
+/// Whether a template uses `component`, as an element or an attribute,
+/// none for selectors of another kind, like classes, which can't tell.
+pub fn is_used(component: &Component, names: &HashSet<String>) -> Option<bool> {
+    match attribute_selector(&component.selector) {
+        Some(attribute) => Some(names.contains(attribute)),
+        None if component.selector.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') => Some(names.contains(&component.selector)),
+        None => None,
+    }
+}